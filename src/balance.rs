@@ -0,0 +1,256 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of ledgeracio.
+//
+// ledgeracio is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// ledgeracio is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ledgeracio.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Token amounts, and their conversion to and from human-readable strings
+
+use super::Error;
+use substrate_subxt::SystemProperties;
+
+/// SI prefixes accepted in front of the token symbol, and the power of ten
+/// they divide by
+const PREFIXES: &[(&str, u8)] = &[("m", 3), ("µ", 6), ("u", 6), ("n", 9), ("p", 12)];
+
+/// An amount of tokens.  The amount is stored in planck, along with the number
+/// of decimals and the token symbol of the chain it belongs to.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Balance {
+    planck: u128,
+    decimals: u8,
+    symbol: String,
+}
+
+impl Balance {
+    /// Creates a [`Balance`] of `planck` planck, using the decimals and symbol
+    /// in `properties`.
+    #[must_use]
+    pub fn new(planck: u128, properties: &SystemProperties) -> Self {
+        Self {
+            planck,
+            decimals: properties.token_decimals,
+            symbol: token_symbol(properties),
+        }
+    }
+
+    /// The amount, in planck
+    #[must_use]
+    pub fn planck(&self) -> u128 { self.planck }
+
+    /// Parse a human-readable amount, such as `1.5`, `1.5 KSM`, or
+    /// `1500 mKSM`.  The result is exact.
+    ///
+    /// # Errors
+    ///
+    /// Fails if:
+    ///
+    /// - The amount is not a decimal number.
+    /// - The amount has more decimal places than the unit allows.
+    /// - The unit is not the token symbol of the chain, optionally preceded by
+    ///   an SI prefix.
+    /// - The amount does not fit in a `u128`.
+    pub fn parse(input: &str, properties: &SystemProperties) -> Result<Self, Error> {
+        let symbol = token_symbol(properties);
+        let input = input.trim();
+        let split = input
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(input.len());
+        let (number, unit) = (&input[..split], input[split..].trim_start());
+        let exponent = unit_exponent(unit, &symbol)?;
+        let scale = properties
+            .token_decimals
+            .checked_sub(exponent)
+            .ok_or_else(|| {
+                format!(
+                    "Unit {} is smaller than the smallest unit of this chain",
+                    unit
+                )
+            })?;
+        let (integer, fraction) = match number.find('.') {
+            Some(dot) => (&number[..dot], &number[dot + 1..]),
+            None => (number, ""),
+        };
+        if integer.is_empty() || (number.contains('.') && fraction.is_empty()) {
+            return Err(format!("Invalid amount {:?}", input).into())
+        }
+        if !fraction.bytes().all(|c| c.is_ascii_digit()) {
+            return Err(format!("Invalid amount {:?}", input).into())
+        }
+        if fraction.len() > scale.into() {
+            return Err(format!(
+                "Amount {:?} has too many decimal places (at most {} allowed)",
+                input, scale
+            )
+            .into())
+        }
+        let overflow = || format!("Amount {:?} is too large", input);
+        let mut planck: u128 = str::parse(integer).map_err(|_| overflow())?;
+        for _ in 0..scale {
+            planck = planck.checked_mul(10).ok_or_else(overflow)?;
+        }
+        if !fraction.is_empty() {
+            let mut fraction_planck: u128 = str::parse(fraction).map_err(|_| overflow())?;
+            for _ in fraction.len()..usize::from(scale) {
+                fraction_planck = fraction_planck.checked_mul(10).ok_or_else(overflow)?;
+            }
+            planck = planck.checked_add(fraction_planck).ok_or_else(overflow)?;
+        }
+        Ok(Self {
+            planck,
+            decimals: properties.token_decimals,
+            symbol,
+        })
+    }
+}
+
+impl std::fmt::Display for Balance {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        let amount = pad(self.decimals, self.planck);
+        if self.symbol.is_empty() {
+            f.write_str(&amount)
+        } else {
+            write!(f, "{} {}", amount, self.symbol)
+        }
+    }
+}
+
+/// The token symbol of the chain, or the empty string if it is not all ASCII
+/// uppercase letters.
+fn token_symbol(properties: &SystemProperties) -> String {
+    let symbol = &properties.token_symbol;
+    if !symbol.is_empty() && symbol.bytes().all(|i| i.is_ascii_uppercase()) {
+        symbol.clone()
+    } else {
+        String::new()
+    }
+}
+
+/// The number of decimal places `unit` is below the base unit `symbol`
+fn unit_exponent(unit: &str, symbol: &str) -> Result<u8, Error> {
+    if unit.is_empty() || unit == symbol {
+        return Ok(0)
+    }
+    if !symbol.is_empty() && unit.ends_with(symbol) {
+        let prefix = &unit[..unit.len() - symbol.len()];
+        for &(name, exponent) in PREFIXES {
+            if name == prefix {
+                return Ok(exponent)
+            }
+        }
+    }
+    Err(if symbol.is_empty() {
+        format!("Unknown unit {}: this chain has no token symbol", unit)
+    } else {
+        format!(
+            "Unknown unit {}: expected {} or a prefixed form such as m{}",
+            unit, symbol, symbol
+        )
+    }
+    .into())
+}
+
+/// Format `value` as a fixed-point number with `zeros` decimal places,
+/// omitting trailing zeros.
+#[must_use]
+pub fn pad(mut zeros: u8, value: u128) -> String {
+    if value == 0 {
+        return "0".to_owned()
+    }
+    let mut value = value.to_string();
+    let len = value.len();
+    assert_ne!(len, 0, "stringified numbers are never empty");
+    if len <= zeros.into() {
+        let mut buf = "0.".to_owned();
+        while len < zeros.into() {
+            buf.push('0');
+            zeros -= 1;
+        }
+        value = buf + &*value
+    } else {
+        value.insert(len - usize::from(zeros), '.');
+    }
+    while value.ends_with('0') {
+        value.pop();
+    }
+    if value.ends_with('.') {
+        value.pop();
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kusama() -> SystemProperties {
+        SystemProperties {
+            ss58_format: 2,
+            token_decimals: 12,
+            token_symbol: "KSM".to_owned(),
+        }
+    }
+
+    fn parse(s: &str) -> Result<u128, Error> { Balance::parse(s, &kusama()).map(|b| b.planck()) }
+
+    #[test]
+    fn padding_works() {
+        assert_eq!(pad(0, 100), "100".to_owned());
+        assert_eq!(pad(3, 100), "0.1".to_owned());
+        assert_eq!(pad(3, 10000), "10".to_owned());
+        assert_eq!(pad(3, 10001), "10.001".to_owned());
+        assert_eq!(pad(3, 10010), "10.01".to_owned());
+    }
+
+    #[test]
+    fn parsing_works() {
+        assert_eq!(parse("1.5").unwrap(), 1_500_000_000_000);
+        assert_eq!(parse("1.5 KSM").unwrap(), 1_500_000_000_000);
+        assert_eq!(parse("1.5KSM").unwrap(), 1_500_000_000_000);
+        assert_eq!(parse("1500 mKSM").unwrap(), 1_500_000_000_000);
+        assert_eq!(parse("0.000000000001").unwrap(), 1);
+        assert_eq!(parse("1 pKSM").unwrap(), 1);
+        assert_eq!(parse("0").unwrap(), 0);
+        assert!(parse("0.0000000000001").is_err());
+        assert!(parse("0.1 pKSM").is_err());
+        assert!(parse("1 DOT").is_err());
+        assert!(parse("1 kKSM").is_err());
+        assert!(parse("").is_err());
+        assert!(parse("1.").is_err());
+        assert!(parse(".1").is_err());
+        assert!(parse("1.2.3").is_err());
+        assert!(parse("340282366920938463463374607431768211455").is_err());
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for &i in &["1.5 KSM", "0.000000000001 KSM", "1000 KSM", "0 KSM"] {
+            assert_eq!(Balance::parse(i, &kusama()).unwrap().to_string(), i);
+        }
+        let no_symbol = SystemProperties {
+            token_symbol: "not a symbol".to_owned(),
+            ..kusama()
+        };
+        assert_eq!(Balance::new(1, &no_symbol).to_string(), "0.000000000001");
+    }
+
+    #[test]
+    fn huge_decimals_do_not_overflow() {
+        let properties = SystemProperties {
+            token_decimals: 40,
+            ..kusama()
+        };
+        assert!(Balance::parse("0.1", &properties).is_err());
+        assert!(Balance::parse("1", &properties).is_err());
+    }
+}
@@ -20,16 +20,8 @@
 #![allow(clippy::non_ascii_literal)]
 #![forbid(unsafe_code)]
 
-mod keyparse;
-mod parser;
-
-/// The version of keys supported
-pub const KEY_VERSION: u8 = 1;
-
-/// The magic number at the beginning of a secret key
-pub const KEY_MAGIC: &[u8] = &*b"Ledgeracio Secret Key";
-
-use ledgeracio::{get_network, Error, HardStore};
+use ledgeracio::{get_network, inspect_allowlist, parse_allowlist, parse_public, parse_secret,
+                 Error, HardStore, KEY_MAGIC, KEY_VERSION};
 use sp_core::crypto::AccountId32 as AccountId;
 use std::{fmt::Debug,
           fs,
@@ -38,8 +30,6 @@ use structopt::StructOpt;
 use substrate_subxt::{sp_core, sp_core::crypto::Ss58AddressFormat};
 
 use ed25519_dalek::Keypair;
-use keyparse::{parse_public, parse_secret};
-use parser::parse as parse_allowlist;
 use std::{fs::OpenOptions, io::Write, os::unix::fs::OpenOptionsExt, path::PathBuf};
 use substrate_subxt::sp_core::H256;
 
@@ -250,7 +240,7 @@ async fn really_inner_main<T: FnOnce() -> Result<ledgeracio::HardStore, Error>>(
                 ),
             });
 
-            for i in crate::parser::inspect::<_, AccountId>(file, network, &pk)? {
+            for i in inspect_allowlist::<_, AccountId>(file, network, &pk)? {
                 writeln!(output, "{}", i)?;
             }
         }
@@ -0,0 +1,47 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of ledgeracio.
+//
+// ledgeracio is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// ledgeracio is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ledgeracio.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Storage items of the BABE pallet, which `substrate_subxt` does not provide
+
+use codec::{Decode, Encode};
+use std::marker::PhantomData;
+use substrate_subxt::{system::System, Store};
+
+const MODULE: &str = "Babe";
+
+/// The current epoch index
+#[derive(Clone, Encode, Decode, Debug, Store)]
+pub struct EpochIndexStore<T: System> {
+    #[store(returns = u64)]
+    /// Runtime marker
+    pub _runtime: PhantomData<T>,
+}
+
+/// The first slot of the chain
+#[derive(Clone, Encode, Decode, Debug, Store)]
+pub struct GenesisSlotStore<T: System> {
+    #[store(returns = u64)]
+    /// Runtime marker
+    pub _runtime: PhantomData<T>,
+}
+
+/// The current slot
+#[derive(Clone, Encode, Decode, Debug, Store)]
+pub struct CurrentSlotStore<T: System> {
+    #[store(returns = u64)]
+    /// Runtime marker
+    pub _runtime: PhantomData<T>,
+}
@@ -17,16 +17,31 @@
 //! Utilities shared by both validator and nominator code
 
 use super::{AccountId, AccountType, Error, LedgeracioPath};
-use substrate_subxt::{sp_core::crypto::{Ss58AddressFormat, Ss58Codec},
-                      staking::{LedgerStore, StakingLedger, ValidatorsStore},
-                      system::AccountStoreExt,
-                      Client, KusamaRuntime, Signer, SystemProperties};
+use crate::{era::{era_progress, format_timestamp, EraProgress},
+            output::Amount,
+            payouts::{format_payouts, payouts_json, unclaimed_payouts, Payouts, UnclaimedEraJson},
+            proof::Verifier,
+            staking::{EraIndex, ErasStakersStore, Exposure, ValidatorPrefs, ValidatorsStore}};
+use codec::Decode;
+use futures::future::{join, join3, try_join_all};
+use ledgeracio::{pad, Balance};
+use serde::Serialize;
+use std::{collections::HashMap, fmt::Write, marker::PhantomData, slice};
+use substrate_subxt::{sp_core::{crypto::{Ss58AddressFormat, Ss58Codec},
+                                storage::{StorageData, StorageKey},
+                                twox_128, H256},
+                      sp_runtime::Perbill,
+                      staking::{ActiveEraStore, LedgerStore, Nominations, NominatorsStore,
+                                RewardDestination, StakingLedger, UnlockChunk},
+                      system::AccountStore,
+                      Client, KusamaRuntime, Signer, Store, SystemProperties};
 
 pub(crate) async fn fetch_validators(
     client: &Client<KusamaRuntime>,
     source: AddressSource<'_>,
     network: Ss58AddressFormat,
     account_type: AccountType,
+    at: At<'_>,
 ) -> Result<Vec<AccountId>, Error> {
     let (index, keystore) = match source {
         AddressSource::Device(index, signer) => (index, signer),
@@ -44,7 +59,12 @@ pub(crate) async fn fetch_validators(
         let path = LedgeracioPath::new(network, account_type, index)?;
         let signer = keystore.signer(path).await?;
         let account_id = signer.account_id();
-        if client.account(account_id, None).await?.data.free == 0 {
+        if fetch_or_default(client, &AccountStore { account_id }, at)
+            .await?
+            .data
+            .free
+            == 0
+        {
             return Ok(v)
         }
         v.push(account_id.clone())
@@ -55,92 +75,569 @@ pub enum AddressSource<'a> {
     Device(Option<u32>, &'a crate::HardStore),
 }
 
+/// The block at which to read the chain state, and how to read and check it
+#[derive(Clone, Copy)]
+pub(crate) struct At<'a> {
+    /// The block, or `None` for the best block
+    pub(crate) hash: Option<H256>,
+    /// If set, every value read is checked against a storage proof, and only
+    /// blocks known to the verifier can be read
+    pub(crate) verifier: Option<&'a Verifier>,
+    /// The connection to the node, shared with the [`Client`]
+    pub(crate) rpc: &'a jsonrpsee::Client,
+}
+
+impl<'a> At<'a> {
+    /// The best block, trusting the node
+    pub(crate) fn best(rpc: &'a jsonrpsee::Client) -> Self {
+        Self {
+            hash: None,
+            verifier: None,
+            rpc,
+        }
+    }
+
+    /// Block `hash`, trusting the node
+    pub(crate) fn block(rpc: &'a jsonrpsee::Client, hash: H256) -> Self {
+        Self {
+            hash: Some(hash),
+            verifier: None,
+            rpc,
+        }
+    }
+}
+
+/// The hash of block `at`, or of the best block if `at` is `None`
+pub(crate) async fn block_or_best(
+    client: &Client<KusamaRuntime>,
+    at: Option<H256>,
+) -> Result<H256, Error> {
+    match at {
+        Some(at) => Ok(at),
+        None => Ok(client
+            .block_hash(None)
+            .await?
+            .ok_or_else(|| "No best block (this is a bug)".to_owned())?),
+    }
+}
+
+/// Fetch many storage items of the same kind at once, using a single RPC call
+/// rather than one per item.  The results are in the same order as `stores`.
+/// If `at` has a verifier, the values are checked against a storage proof,
+/// and the keys are built without trusting the metadata.
+pub(crate) async fn fetch_many<F: Store<KusamaRuntime>>(
+    client: &Client<KusamaRuntime>,
+    stores: &[F],
+    at: At<'_>,
+) -> Result<Vec<Option<F::Returns>>, Error> {
+    if stores.is_empty() {
+        return Ok(vec![])
+    }
+    let metadata = client.metadata();
+    let keys = match at.verifier {
+        Some(_) => stores
+            .iter()
+            .map(|store| crate::layout::key(store, metadata))
+            .collect::<Result<Vec<_>, _>>()?,
+        None => stores
+            .iter()
+            .map(|store| store.key(metadata))
+            .collect::<Result<Vec<_>, _>>()?,
+    };
+    let hash = block_or_best(client, at.hash).await?;
+    let values = match at.verifier {
+        Some(verifier) => verifier.fetch(&keys, hash).await?,
+        None => fetch_raw(at.rpc, &keys, hash).await?,
+    };
+    values
+        .into_iter()
+        .map(|data| -> Result<_, Error> {
+            match data {
+                None => Ok(None),
+                Some(data) => Ok(Some(Decode::decode(&mut &*data.0)?)),
+            }
+        })
+        .collect()
+}
+
+/// Fetch a single storage item at block `at`
+pub(crate) async fn fetch<F: Store<KusamaRuntime>>(
+    client: &Client<KusamaRuntime>,
+    store: &F,
+    at: At<'_>,
+) -> Result<Option<F::Returns>, Error> {
+    Ok(fetch_many(client, slice::from_ref(store), at)
+        .await?
+        .pop()
+        .flatten())
+}
+
+/// Fetch a single storage item at block `at`, or its default value if it is
+/// not set.  If `at` has a verifier, the default value does not come from the
+/// metadata.
+pub(crate) async fn fetch_or_default<F: Store<KusamaRuntime>>(
+    client: &Client<KusamaRuntime>,
+    store: &F,
+    at: At<'_>,
+) -> Result<F::Returns, Error> {
+    match (fetch(client, store, at).await?, at.verifier) {
+        (Some(value), _) => Ok(value),
+        (None, Some(_)) => crate::layout::default::<F>(),
+        (None, None) => Ok(store.default(client.metadata())?),
+    }
+}
+
+/// Fetch the undecoded values of many storage keys at block `at`, using a
+/// single RPC call.  The results are in the same order as `keys`.
+pub(crate) async fn fetch_raw(
+    rpc: &jsonrpsee::Client,
+    keys: &[StorageKey],
+    at: H256,
+) -> Result<Vec<Option<StorageData>>, Error> {
+    if keys.is_empty() {
+        return Ok(vec![])
+    }
+    let mut values: HashMap<_, _> = crate::rpc::query_storage_at(rpc, keys, at)
+        .await?
+        .into_iter()
+        .collect();
+    Ok(keys
+        .iter()
+        .map(|key| values.remove(key).flatten())
+        .collect())
+}
+
+/// The number of storage items to fetch with a single call to [`fetch_many`]
+/// when fetching many items
+pub(crate) const FETCH_CHUNK: usize = 1000;
+
+/// Like [`fetch_many`], but for more items than fit in a single RPC call.  The
+/// items are fetched [`FETCH_CHUNK`] at a time, all at the same block.
+pub(crate) async fn fetch_chunked<F: Store<KusamaRuntime>>(
+    client: &Client<KusamaRuntime>,
+    stores: &[F],
+    at: At<'_>,
+) -> Result<Vec<Option<F::Returns>>, Error> {
+    let at = At {
+        hash: Some(block_or_best(client, at.hash).await?),
+        ..at
+    };
+    let mut values = Vec::with_capacity(stores.len());
+    for chunk in stores.chunks(FETCH_CHUNK) {
+        values.extend(fetch_many(client, chunk, at).await?)
+    }
+    Ok(values)
+}
+
+/// Fetch the nominations of every nominator on the chain at block `at`.  This
+/// is slow, as it reads the whole `Nominators` map.  The list of nominators
+/// cannot be checked against storage proofs, so `at` must not have a
+/// verifier.
+pub(crate) async fn all_nominations(
+    client: &Client<KusamaRuntime>,
+    at: At<'_>,
+) -> Result<Vec<(AccountId, Nominations<AccountId>)>, Error> {
+    if at.verifier.is_some() {
+        return Err("The list of all nominators cannot be verified".into())
+    }
+    let prefix = StorageKey([twox_128(b"Staking"), twox_128(b"Nominators")].concat());
+    let hash = block_or_best(client, at.hash).await?;
+    // The map is keyed by `Twox64Concat`, so each key ends with the stash
+    // account after an 8-byte hash.
+    let stores: Vec<_> = crate::rpc::storage_keys(at.rpc, &prefix, hash)
+        .await?
+        .into_iter()
+        .filter_map(|key| {
+            let mut stash = key.0.get(prefix.0.len() + 8..)?;
+            AccountId::decode(&mut stash).ok()
+        })
+        .map(|stash| NominatorsStore { stash })
+        .collect();
+    let nominations = fetch_chunked(client, &stores, At::block(at.rpc, hash)).await?;
+    Ok(stores
+        .into_iter()
+        .zip(nominations)
+        .filter_map(|(store, nominations)| Some((store.stash, nominations?)))
+        .collect())
+}
+
+/// What is shown about a validator
+struct ValidatorInfo {
+    controller: AccountId,
+    /// The staking ledger, or `None` if `controller` is not a controller
+    ledger: Option<StakingLedger<AccountId, u128>>,
+    /// The unclaimed payouts, if there is a ledger
+    payouts: Option<Payouts>,
+    /// The preferences, or `None` if the validator is not validating
+    prefs: Option<ValidatorPrefs>,
+}
+
+/// Fetch what is shown about each validator controlled by `controllers`, in the
+/// same order as `controllers`, along with the progress of the current era
+async fn fetch_validator_info(
+    client: &Client<KusamaRuntime>,
+    controllers: &[AccountId],
+    at: At<'_>,
+) -> Result<(Vec<ValidatorInfo>, EraProgress), Error> {
+    let stores: Vec<_> = controllers
+        .iter()
+        .map(|controller| LedgerStore {
+            controller: controller.clone(),
+        })
+        .collect();
+    let ledgers = fetch_many(client, &stores, at).await?;
+    let stores: Vec<_> = ledgers
+        .iter()
+        .flatten()
+        .map(|ledger| ValidatorsStore {
+            stash: ledger.stash.clone(),
+        })
+        .collect();
+    let payouts = controllers
+        .iter()
+        .zip(&ledgers)
+        .filter(|(_, ledger)| ledger.is_some())
+        .map(|(controller, _)| unclaimed_payouts(controller.clone(), client, at));
+    let (prefs, payouts, progress) = join3(
+        fetch_many(client, &stores, at),
+        try_join_all(payouts),
+        era_progress(client, at),
+    )
+    .await;
+    let (mut prefs, mut payouts) = (prefs?.into_iter(), payouts?.into_iter());
+    let info = controllers
+        .iter()
+        .zip(ledgers)
+        .map(|(controller, ledger)| {
+            let (payouts, prefs) = match ledger {
+                None => (None, None),
+                Some(_) => (
+                    Some(payouts.next().expect("one payout table per ledger")),
+                    prefs.next().expect("one preference per ledger"),
+                ),
+            };
+            ValidatorInfo {
+                controller: controller.clone(),
+                ledger,
+                payouts,
+                prefs,
+            }
+        })
+        .collect();
+    Ok((info, progress?))
+}
+
+/// Format the status of each validator controlled by `controllers`.  The
+/// output is in the same order as `controllers`.
 pub(crate) async fn display_validators(
     client: &Client<KusamaRuntime>,
-    nominations: &[AccountId],
+    controllers: &[AccountId],
     network: Ss58AddressFormat,
-) -> Result<(), Error> {
-    for controller in nominations {
-        let store = LedgerStore {
-            controller: controller.clone(),
-        };
-        match client.fetch(&store, None).await? {
-            None => println!(
-                "validator {} not found",
-                controller.to_ss58check_with_version(network)
-            ),
-            Some(StakingLedger {
-                stash,
-                total,
-                active,
-                unlocking,
-                claimed_rewards: _,
-            }) => {
-                let SystemProperties {
-                    token_decimals,
-                    mut token_symbol,
-                    ..
-                } = client.properties().clone();
-                let mut good_symbol = true;
-                for i in token_symbol.bytes() {
-                    good_symbol &= i.is_ascii_uppercase()
-                }
-                if !good_symbol {
-                    token_symbol = "".to_owned()
-                }
-                println!(
-                    "    Validator account: {}\n    Stash balance: {} {sym}\n    Amount at stake: \
-                     {} {sym}\nEras with unclaimed payouts: {:?}\n    Amount unlocking: {:?}",
-                    stash.to_ss58check_with_version(network),
-                    pad(token_decimals, total),
-                    pad(token_decimals, active),
-                    super::payouts::display_payouts(controller.clone(), client).await?,
+    at: At<'_>,
+) -> Result<String, Error> {
+    Ok(display_each_validator(client, controllers, network, at)
+        .await?
+        .concat())
+}
+
+/// Like [`display_validators`], but with the status of each validator
+/// formatted separately
+pub(crate) async fn display_each_validator(
+    client: &Client<KusamaRuntime>,
+    controllers: &[AccountId],
+    network: Ss58AddressFormat,
+    at: At<'_>,
+) -> Result<Vec<String>, Error> {
+    let (info, progress) = fetch_validator_info(client, controllers, at).await?;
+    let properties = client.properties();
+    let mut outputs = vec![];
+    for ValidatorInfo {
+        controller,
+        ledger,
+        payouts,
+        prefs,
+    } in info
+    {
+        let mut output = String::new();
+        match (ledger, payouts) {
+            (
+                Some(StakingLedger {
+                    stash,
+                    total,
+                    active,
                     unlocking,
-                    sym = token_symbol
-                );
-                let store = ValidatorsStore {
-                    stash: stash.clone(),
-                };
-                match client.fetch(&store, None).await? {
-                    None => println!(
+                    claimed_rewards: _,
+                }),
+                Some(payouts),
+            ) => {
+                writeln!(
+                    output,
+                    "    Validator account: {}\n    Stash balance: {}\n    Amount at stake: \
+                     {}\n{}{}",
+                    stash.to_ss58check_with_version(network),
+                    Balance::new(total, properties),
+                    Balance::new(active, properties),
+                    format_payouts(&payouts, properties),
+                    format_unlocking(&unlocking, &progress, properties, "    "),
+                )?;
+                match prefs {
+                    None => writeln!(
+                        output,
                         "    validator {} has no preferences ― it is probably inactive\n",
                         stash.to_ss58check_with_version(network)
-                    ),
-                    Some(prefs) => println!(
-                        "    Commission: {}%\n",
-                        pad(9, u128::from(prefs.commission.deconstruct()) * 100)
-                    ),
+                    )?,
+                    Some(prefs) => writeln!(
+                        output,
+                        "    Commission: {}\n",
+                        format_commission(prefs.commission)
+                    )?,
                 }
             }
+            _ => writeln!(
+                output,
+                "validator {} not found",
+                controller.to_ss58check_with_version(network)
+            )?,
         }
+        outputs.push(output)
     }
-    Ok(())
+    Ok(outputs)
 }
 
-pub fn pad(mut zeros: u8, value: u128) -> String {
-    if value == 0 {
-        return "0".to_owned()
+/// The status of a validator, as JSON
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ValidatorJson {
+    controller: String,
+    /// The stash, or `null` if `controller` is not a controller.  All other
+    /// fields are then `null` or empty.
+    stash: Option<String>,
+    total: Option<Amount>,
+    active: Option<Amount>,
+    unlocking: Vec<UnlockChunkJson>,
+    /// The commission in parts per billion, or `null` if the validator is not
+    /// validating
+    commission_ppb: Option<u32>,
+    /// Whether the validator blocks new nominations, or `null` if it is not
+    /// validating
+    blocked: Option<bool>,
+    unclaimed_payouts: Vec<UnclaimedEraJson>,
+}
+
+/// Fetch the status of each validator controlled by `controllers` as JSON, in
+/// the same order as `controllers`
+pub(crate) async fn validators_json(
+    client: &Client<KusamaRuntime>,
+    controllers: &[AccountId],
+    network: Ss58AddressFormat,
+    at: At<'_>,
+) -> Result<Vec<ValidatorJson>, Error> {
+    let (info, progress) = fetch_validator_info(client, controllers, at).await?;
+    let properties = client.properties();
+    Ok(info
+        .into_iter()
+        .map(|info| {
+            let ledger = info.ledger.as_ref();
+            ValidatorJson {
+                controller: info.controller.to_ss58check_with_version(network),
+                stash: ledger.map(|ledger| ledger.stash.to_ss58check_with_version(network)),
+                total: ledger.map(|ledger| Amount::new(ledger.total, properties)),
+                active: ledger.map(|ledger| Amount::new(ledger.active, properties)),
+                unlocking: ledger.map_or_else(Vec::new, |ledger| {
+                    unlocking_json(&ledger.unlocking, &progress, properties)
+                }),
+                commission_ppb: info
+                    .prefs
+                    .as_ref()
+                    .map(|prefs| prefs.commission.deconstruct()),
+                blocked: info.prefs.as_ref().map(|prefs| prefs.blocked),
+                unclaimed_payouts: info
+                    .payouts
+                    .as_ref()
+                    .map_or_else(Vec::new, |payouts| payouts_json(payouts, properties)),
+            }
+        })
+        .collect())
+}
+
+/// Format the unlocking chunks of a staking ledger, along with when each can
+/// be withdrawn and the total that can be withdrawn now.  Each line is
+/// prefixed with `indent`.
+pub(crate) fn format_unlocking(
+    unlocking: &[UnlockChunk<u128>],
+    progress: &EraProgress,
+    properties: &SystemProperties,
+    indent: &str,
+) -> String {
+    if unlocking.is_empty() {
+        return format!("{}Amount unlocking: none\n", indent)
     }
-    let mut value = value.to_string();
-    let len = value.len();
-    assert_ne!(len, 0, "stringified numbers are never empty");
-    if len <= zeros.into() {
-        let mut buf = "0.".to_owned();
-        while len < zeros.into() {
-            buf.push('0');
-            zeros -= 1;
+    let mut output = format!("{}Amount unlocking:\n", indent);
+    let mut withdrawable = 0_u128;
+    for &UnlockChunk { value, era } in unlocking {
+        output += &match progress.unlocks_at(era) {
+            None => {
+                withdrawable = withdrawable.saturating_add(value);
+                format!(
+                    "{}    {} (unlocked in era {})\n",
+                    indent,
+                    Balance::new(value, properties),
+                    era
+                )
+            }
+            Some(start) => format!(
+                "{}    {} unlocks in era {} ({} era(s) from now, about {})\n",
+                indent,
+                Balance::new(value, properties),
+                era,
+                era - progress.active_era,
+                format_timestamp(start)
+            ),
         }
-        value = buf + &*value
-    } else {
-        value.insert(len - usize::from(zeros), '.');
     }
-    while value.ends_with('0') {
-        value.pop();
+    output += &format!(
+        "{}Withdrawable now: {}\n",
+        indent,
+        Balance::new(withdrawable, properties)
+    );
+    output
+}
+
+/// A chunk of funds being unbonded, as JSON
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct UnlockChunkJson {
+    amount: Amount,
+    /// The era in which the chunk unlocks
+    era: EraIndex,
+    /// Whether the chunk has unlocked, and so can be withdrawn
+    unlocked: bool,
+    /// An estimate of when the chunk will unlock, in milliseconds since the
+    /// Unix epoch, or `null` if it has already unlocked
+    unlocks_at: Option<u64>,
+}
+
+/// Convert the unlocking chunks of a staking ledger to JSON
+pub(crate) fn unlocking_json(
+    unlocking: &[UnlockChunk<u128>],
+    progress: &EraProgress,
+    properties: &SystemProperties,
+) -> Vec<UnlockChunkJson> {
+    unlocking
+        .iter()
+        .map(|&UnlockChunk { value, era }| {
+            let unlocks_at = progress.unlocks_at(era);
+            UnlockChunkJson {
+                amount: Amount::new(value, properties),
+                era,
+                unlocked: unlocks_at.is_none(),
+                unlocks_at,
+            }
+        })
+        .collect()
+}
+
+/// Whether a validator is in the active set
+#[derive(Debug)]
+pub(crate) enum ValidatorStatus {
+    /// In the active set, with the given exposure
+    Active(Exposure<AccountId, u128>),
+    /// Not in the active set, but intending to validate
+    Waiting,
+    /// Not intending to validate
+    Chilled,
+}
+
+/// The index of the active era at block `at`
+pub(crate) async fn active_era(
+    client: &Client<KusamaRuntime>,
+    at: At<'_>,
+) -> Result<EraIndex, Error> {
+    let store = ActiveEraStore {
+        _runtime: PhantomData,
+    };
+    Ok(fetch(client, &store, at)
+        .await?
+        .ok_or_else(|| "No active era (this is a bug)".to_owned())?
+        .index)
+}
+
+/// The status of each validator with a stash in `stashes` in era `era`, along
+/// with its preferences at block `at`, if it has any.  The results are in the
+/// same order as `stashes`.
+pub(crate) async fn validator_statuses(
+    client: &Client<KusamaRuntime>,
+    stashes: &[AccountId],
+    era: EraIndex,
+    at: At<'_>,
+) -> Result<Vec<(ValidatorStatus, Option<ValidatorPrefs>)>, Error> {
+    let exposures: Vec<_> = stashes
+        .iter()
+        .map(|stash| ErasStakersStore {
+            era,
+            stash: stash.clone(),
+        })
+        .collect();
+    let prefs: Vec<_> = stashes
+        .iter()
+        .map(|stash| ValidatorsStore {
+            stash: stash.clone(),
+        })
+        .collect();
+    let (exposures, prefs) = join(
+        fetch_many(client, &exposures, at),
+        fetch_many(client, &prefs, at),
+    )
+    .await;
+    Ok(exposures?
+        .into_iter()
+        .zip(prefs?)
+        .map(|(exposure, prefs)| {
+            let status = match exposure {
+                Some(exposure) if exposure.total != 0 => ValidatorStatus::Active(exposure),
+                _ if prefs.is_some() => ValidatorStatus::Waiting,
+                _ => ValidatorStatus::Chilled,
+            };
+            (status, prefs)
+        })
+        .collect())
+}
+
+/// Format a commission as a percentage
+pub(crate) fn format_commission(commission: Perbill) -> String {
+    format!("{}%", pad(9, u128::from(commission.deconstruct()) * 100))
+}
+
+/// Format a reward destination for display, showing account destinations as
+/// SS58 addresses for `network`.
+pub(crate) fn format_reward_destination(
+    destination: &RewardDestination<AccountId>,
+    network: Ss58AddressFormat,
+) -> String {
+    match destination {
+        RewardDestination::Staked => "staked (added to the amount at stake)".to_owned(),
+        RewardDestination::Stash => "stash account (not staked)".to_owned(),
+        RewardDestination::Controller => "controller account (not staked)".to_owned(),
+        RewardDestination::Account(account) => format!(
+            "account {} (not staked)",
+            account.to_ss58check_with_version(network)
+        ),
+        RewardDestination::None => "none (rewards are not paid out)".to_owned(),
     }
-    if value.ends_with('.') {
-        value.pop();
+}
+
+/// Format a reward destination in the form accepted by `set-payee`
+pub(crate) fn payee_code(
+    destination: &RewardDestination<AccountId>,
+    network: Ss58AddressFormat,
+) -> String {
+    match destination {
+        RewardDestination::Staked => "staked".to_owned(),
+        RewardDestination::Stash => "stash".to_owned(),
+        RewardDestination::Controller => "controller".to_owned(),
+        RewardDestination::Account(account) => {
+            format!("account:{}", account.to_ss58check_with_version(network))
+        }
+        RewardDestination::None => "none".to_owned(),
     }
-    value
 }
 
 pub fn parse_ppb(data: &str) -> Result<u32, Error> {
@@ -180,15 +677,6 @@ pub fn parse_ppb(data: &str) -> Result<u32, Error> {
 mod tests {
     use super::*;
 
-    #[test]
-    fn padding_works() {
-        assert_eq!(pad(0, 100), "100".to_owned());
-        assert_eq!(pad(3, 100), "0.1".to_owned());
-        assert_eq!(pad(3, 10000), "10".to_owned());
-        assert_eq!(pad(3, 10001), "10.001".to_owned());
-        assert_eq!(pad(3, 10010), "10.01".to_owned());
-    }
-
     #[test]
     fn parse_ppb_works() {
         assert_eq!(parse_ppb("1.000000000").unwrap(), 1_000_000_000);
@@ -0,0 +1,413 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of ledgeracio.
+//
+// ledgeracio is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// ledgeracio is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ledgeracio.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Checks of device accounts for common misconfigurations
+
+use super::{AccountType, Error, HardStore};
+use crate::{common::{fetch, fetch_many, fetch_or_default, fetch_validators, validator_statuses,
+                     AddressSource, At, ValidatorStatus},
+            era::{constant, era_progress},
+            im_online::{AuthoredBlocksStore, ReceivedHeartbeatsStore},
+            output::{print_json, OutputFormat},
+            payouts::{unclaimed_nominator_payouts, unclaimed_payouts, NominatorPayout, Payouts,
+                      EXPIRY_WARNING_ERAS},
+            rewards::era_bounds,
+            session::{NextKeysStore, SessionIndex, ValidatorsStore},
+            staking::{EraIndex, UnappliedSlash, UnappliedSlashesStore}};
+use futures::future::{join, try_join_all};
+use ledgeracio::Balance;
+use serde::Serialize;
+use std::{convert::TryFrom, fmt, marker::PhantomData};
+use substrate_subxt::{sp_core::crypto::{AccountId32 as AccountId, Ss58AddressFormat, Ss58Codec},
+                      staking::{BondedStore, LedgerStore, NominatorsStore, PayeeStore,
+                                RewardDestination, StakingLedger},
+                      system::AccountStore,
+                      Client, KusamaRuntime};
+
+/// How urgent a problem is.  More urgent problems sort first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Severity {
+    /// Funds are at risk, or the account is not staking at all
+    Critical,
+    /// Rewards are being lost, or soon will be
+    Warning,
+    /// Worth looking at, but not urgent
+    Notice,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Critical => "CRITICAL",
+            Self::Warning => "WARNING",
+            Self::Notice => "NOTICE",
+        })
+    }
+}
+
+/// A problem with a device account
+#[derive(Serialize)]
+struct Problem {
+    severity: Severity,
+    /// The account with the problem, such as `validator 1 (<address>)`
+    account: String,
+    description: String,
+    /// What to do about the problem, usually a ledgeracio command
+    suggestion: Option<String>,
+}
+
+/// Chain state that is shared by the checks of every account
+struct ChainState<'a> {
+    /// The block to check at
+    at: At<'a>,
+    active_era: EraIndex,
+    /// The oldest era for which payouts can still be claimed
+    history_start: EraIndex,
+    session: SessionIndex,
+    /// The validators of the current session, in the order used by I’m Online
+    session_validators: Vec<AccountId>,
+    /// Whether enough of the session has passed that validators should have
+    /// sent a heartbeat
+    heartbeats_due: bool,
+    /// The slashes that have yet to be applied
+    slashes: Vec<UnappliedSlash<AccountId, u128>>,
+}
+
+async fn chain_state<'a>(
+    client: &Client<KusamaRuntime>,
+    at: At<'a>,
+) -> Result<ChainState<'a>, Error> {
+    let (progress, bounds) = join(era_progress(client, at), era_bounds(client, at)).await;
+    let (progress, (history_start, _)) = (progress?, bounds?);
+    // Older runtimes do not defer slashes.
+    let defer: EraIndex = constant(client, "Staking", "SlashDeferDuration").unwrap_or_default();
+    let slashes: Vec<_> = (progress.active_era..=progress.active_era.saturating_add(defer))
+        .map(|era| UnappliedSlashesStore {
+            era,
+            _runtime: PhantomData,
+        })
+        .collect();
+    let validators = ValidatorsStore {
+        _runtime: PhantomData,
+    };
+    let (session_validators, slashes) = join(
+        fetch_or_default(client, &validators, at),
+        fetch_many(client, &slashes, at),
+    )
+    .await;
+    Ok(ChainState {
+        at,
+        active_era: progress.active_era,
+        history_start,
+        session: progress.session,
+        session_validators: session_validators?,
+        heartbeats_due: progress.slots_into_session.saturating_mul(2) > progress.session_length,
+        slashes: slashes?.into_iter().flatten().flatten().collect(),
+    })
+}
+
+/// Check the account at `index` of type `account_type`, which has address
+/// `controller`
+async fn check_account(
+    client: &Client<KusamaRuntime>,
+    state: &ChainState<'_>,
+    account_type: AccountType,
+    index: u32,
+    controller: AccountId,
+    network: Ss58AddressFormat,
+) -> Result<Vec<Problem>, Error> {
+    let kind = match account_type {
+        AccountType::Validator => "validator",
+        AccountType::Nominator => "nominator",
+    };
+    let account = format!(
+        "{} {} ({})",
+        kind,
+        index,
+        controller.to_ss58check_with_version(network)
+    );
+    let mut problems = vec![];
+    let mut problem = |severity, description: String, suggestion: Option<String>| {
+        problems.push(Problem {
+            severity,
+            account: account.clone(),
+            description,
+            suggestion,
+        })
+    };
+    let ledger = LedgerStore {
+        controller: controller.clone(),
+    };
+    let StakingLedger { stash, .. } = match fetch(client, &ledger, state.at).await? {
+        Some(ledger) => ledger,
+        None => {
+            problem(
+                Severity::Critical,
+                "is not the controller of any stash".to_owned(),
+                Some(format!(
+                    "bond a stash with the address from `ledgeracio {} address {}` as its \
+                     controller",
+                    kind, index
+                )),
+            );
+            return Ok(problems)
+        }
+    };
+    let address = stash.to_ss58check_with_version(network);
+    let bonded = BondedStore {
+        stash: stash.clone(),
+    };
+    let payee = PayeeStore {
+        stash: stash.clone(),
+    };
+    let (bonded, payee) = join(
+        fetch(client, &bonded, state.at),
+        fetch(client, &payee, state.at),
+    )
+    .await;
+    if bonded? != Some(controller.clone()) {
+        problem(
+            Severity::Critical,
+            format!("is not the controller of its stash {}", address),
+            Some(format!(
+                "set the controller of {} to this account, or stop using this account",
+                address
+            )),
+        )
+    }
+    if let Some(RewardDestination::Account(destination)) = payee? {
+        let data = fetch_or_default(
+            client,
+            &AccountStore {
+                account_id: &destination,
+            },
+            state.at,
+        )
+        .await?
+        .data;
+        if data.free == 0 && data.reserved == 0 {
+            problem(
+                Severity::Warning,
+                format!(
+                    "pays rewards to {}, which has no balance and may not exist",
+                    destination.to_ss58check_with_version(network)
+                ),
+                Some(format!("ledgeracio {} set-payee {} stash", kind, index)),
+            )
+        }
+    }
+    let properties = client.properties();
+    for slash in &state.slashes {
+        let amount = if slash.validator == stash {
+            slash.own
+        } else if let Some(&(_, amount)) = slash.others.iter().find(|(who, _)| *who == stash) {
+            amount
+        } else {
+            continue
+        };
+        problem(
+            Severity::Critical,
+            format!(
+                "has a pending slash of {} from validator {}",
+                Balance::new(amount, properties),
+                slash.validator.to_ss58check_with_version(network)
+            ),
+            Some("only governance can cancel a pending slash".to_owned()),
+        )
+    }
+    match account_type {
+        AccountType::Validator => {
+            let statuses = validator_statuses(client, &[stash.clone()], state.active_era, state.at);
+            let next_keys = NextKeysStore {
+                validator_id: stash.clone(),
+            };
+            let (statuses, next_keys) = join(statuses, fetch(client, &next_keys, state.at)).await;
+            match statuses?.pop().map(|(status, _)| status) {
+                Some(ValidatorStatus::Active(_)) => {}
+                Some(ValidatorStatus::Waiting) => problem(
+                    Severity::Warning,
+                    "is not in the active set (waiting to be elected)".to_owned(),
+                    Some(format!("ledgeracio validator show {} --nominators", index)),
+                ),
+                Some(ValidatorStatus::Chilled) | None => problem(
+                    Severity::Critical,
+                    "is not validating (chilled)".to_owned(),
+                    Some(format!("ledgeracio validator announce {}", index)),
+                ),
+            }
+            if next_keys?.is_none() {
+                problem(
+                    Severity::Critical,
+                    "has no session keys set".to_owned(),
+                    Some(format!(
+                        "ledgeracio validator rotate-keys {} --node <node>",
+                        index
+                    )),
+                )
+            }
+            let authority_index = state
+                .session_validators
+                .iter()
+                .position(|validator| *validator == stash);
+            if let (Some(authority_index), true) = (authority_index, state.heartbeats_due) {
+                let heartbeat = ReceivedHeartbeatsStore {
+                    session: state.session,
+                    authority_index: u32::try_from(authority_index)?,
+                    _runtime: PhantomData,
+                };
+                let blocks = AuthoredBlocksStore {
+                    session: state.session,
+                    validator_id: stash.clone(),
+                };
+                let (heartbeat, blocks) = join(
+                    fetch(client, &heartbeat, state.at),
+                    fetch_or_default(client, &blocks, state.at),
+                )
+                .await;
+                if heartbeat?.is_none() && blocks? == 0 {
+                    problem(
+                        Severity::Warning,
+                        format!(
+                            "has sent no heartbeat and authored no blocks in session {}, so its \
+                             node may be offline",
+                            state.session
+                        ),
+                        Some(format!(
+                            "ledgeracio validator check-keys {} --node <node>",
+                            index
+                        )),
+                    )
+                }
+            }
+            let Payouts {
+                eras,
+                history_start,
+                ..
+            } = unclaimed_payouts(controller, client, state.at).await?;
+            for unclaimed in eras.iter().filter(|unclaimed| unclaimed.reward.is_some()) {
+                let remaining = unclaimed.era - history_start;
+                if remaining < EXPIRY_WARNING_ERAS {
+                    problem(
+                        Severity::Warning,
+                        format!(
+                            "has an unclaimed payout for era {} that expires in {} era(s)",
+                            unclaimed.era,
+                            remaining + 1
+                        ),
+                        Some(format!(
+                            "ledgeracio validator payout --index {} --era {}",
+                            index, unclaimed.era
+                        )),
+                    )
+                }
+            }
+        }
+        AccountType::Nominator => {
+            let nominations = NominatorsStore {
+                stash: stash.clone(),
+            };
+            if fetch(client, &nominations, state.at).await?.is_none() {
+                problem(
+                    Severity::Notice,
+                    "is not nominating".to_owned(),
+                    Some(format!(
+                        "ledgeracio nominator nominate {} <validators>",
+                        index
+                    )),
+                )
+            }
+            let (_, payouts) = unclaimed_nominator_payouts(controller, client, state.at).await?;
+            for NominatorPayout {
+                era,
+                validator,
+                share,
+                oversubscribed,
+            } in payouts
+            {
+                let remaining = era.saturating_sub(state.history_start);
+                if share.is_some() && !oversubscribed && remaining < EXPIRY_WARNING_ERAS {
+                    problem(
+                        Severity::Warning,
+                        format!(
+                            "has an unclaimed payout from validator {} for era {} that expires in \
+                             {} era(s)",
+                            validator.to_ss58check_with_version(network),
+                            era,
+                            remaining + 1
+                        ),
+                        Some("ask the validator to claim it".to_owned()),
+                    )
+                }
+            }
+        }
+    }
+    Ok(problems)
+}
+
+/// Check every account on the device, and print the problems found, most
+/// urgent first
+pub(crate) async fn main(
+    client: &Client<KusamaRuntime>,
+    keystore: &HardStore,
+    network: Ss58AddressFormat,
+    output: OutputFormat,
+    at: At<'_>,
+) -> Result<(), Error> {
+    let state = chain_state(client, at).await?;
+    let mut checks = vec![];
+    for &account_type in &[AccountType::Validator, AccountType::Nominator] {
+        let controllers = fetch_validators(
+            client,
+            AddressSource::Device(None, keystore),
+            network,
+            account_type,
+            at,
+        )
+        .await?;
+        for (index, controller) in (1..).zip(controllers) {
+            checks.push(check_account(
+                client,
+                &state,
+                account_type,
+                index,
+                controller,
+                network,
+            ))
+        }
+    }
+    let mut problems: Vec<_> = try_join_all(checks).await?.into_iter().flatten().collect();
+    // The sort is stable, so problems of the same severity stay in account
+    // order.
+    problems.sort_by_key(|problem| problem.severity);
+    if output == OutputFormat::Json {
+        return print_json(&serde_json::json!({ "problems": problems }))
+    }
+    if problems.is_empty() {
+        println!("No problems found");
+        return Ok(())
+    }
+    for (number, problem) in (1..).zip(problems) {
+        println!(
+            "{}. [{}] {} {}",
+            number, problem.severity, problem.account, problem.description
+        );
+        if let Some(suggestion) = problem.suggestion {
+            println!("   Suggestion: {}", suggestion)
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,385 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of ledgeracio.
+//
+// ledgeracio is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// ledgeracio is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ledgeracio.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Era and session progress
+
+use crate::{babe::{CurrentSlotStore, EpochIndexStore, GenesisSlotStore},
+            common::{fetch, fetch_or_default, At},
+            session::{CurrentIndexStore, SessionIndex},
+            staking::{EraIndex, ErasStartSessionIndexStore},
+            Error};
+use futures::future::{join, join4};
+use serde::Serialize;
+use std::{fmt::Write, marker::PhantomData};
+use substrate_subxt::{staking::{ActiveEraStore, CurrentEraStore},
+                      Client, KusamaRuntime};
+
+/// The progress of the chain through the current era and session
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct EraProgress {
+    /// The index of the active era
+    pub(crate) active_era: EraIndex,
+    /// When the active era started, in milliseconds since the Unix epoch, if
+    /// known
+    pub(crate) active_era_start: Option<u64>,
+    /// The index of the current (planned) era.  This is ahead of the active
+    /// era once the next validator set has been elected.
+    pub(crate) current_era: EraIndex,
+    /// The index of the current session
+    pub(crate) session: SessionIndex,
+    /// The number of sessions of the active era that have already ended
+    pub(crate) sessions_into_era: u32,
+    /// The number of sessions in an era
+    pub(crate) sessions_per_era: u32,
+    /// The number of slots of the current session that have already passed
+    pub(crate) slots_into_session: u64,
+    /// The number of slots in a session
+    pub(crate) session_length: u64,
+    /// The duration of a slot, in milliseconds
+    pub(crate) slot_duration: u64,
+    /// The current time, in milliseconds since the Unix epoch, as estimated
+    /// from the current slot
+    pub(crate) now: u64,
+}
+
+impl EraProgress {
+    /// The estimated time until the next session, in milliseconds
+    pub(crate) fn until_next_session(&self) -> u64 {
+        self.session_length
+            .saturating_sub(self.slots_into_session)
+            .saturating_mul(self.slot_duration)
+    }
+
+    /// The estimated time until the next era, in milliseconds
+    pub(crate) fn until_next_era(&self) -> u64 {
+        let sessions_left = self
+            .sessions_per_era
+            .saturating_sub(self.sessions_into_era)
+            .saturating_sub(1);
+        u64::from(sessions_left)
+            .saturating_mul(self.session_length)
+            .saturating_mul(self.slot_duration)
+            .saturating_add(self.until_next_session())
+    }
+
+    /// The estimated duration of an era, in milliseconds
+    pub(crate) fn era_duration(&self) -> u64 {
+        u64::from(self.sessions_per_era)
+            .saturating_mul(self.session_length)
+            .saturating_mul(self.slot_duration)
+    }
+
+    /// The estimated time at which era `era` will start, in milliseconds since
+    /// the Unix epoch, or `None` if it has already started
+    pub(crate) fn era_start(&self, era: EraIndex) -> Option<u64> {
+        let eras_after_next = era.checked_sub(self.active_era)?.checked_sub(1)?;
+        Some(
+            u64::from(eras_after_next)
+                .saturating_mul(self.era_duration())
+                .saturating_add(self.until_next_era())
+                .saturating_add(self.now),
+        )
+    }
+
+    /// Whether funds that unlock in era `era` can be withdrawn.  This depends
+    /// on the current era, which is planned a session before it becomes
+    /// active.
+    pub(crate) fn unlocked(&self, era: EraIndex) -> bool { era <= self.current_era }
+
+    /// When funds that unlock in era `era` can be withdrawn, in milliseconds
+    /// since the Unix epoch, or `None` if they already can be
+    pub(crate) fn unlocks_at(&self, era: EraIndex) -> Option<u64> {
+        if self.unlocked(era) {
+            None
+        } else {
+            self.era_start(era)
+        }
+    }
+
+    /// The progress through the active era, in percent
+    pub(crate) fn percent(&self) -> u64 {
+        let era_length = u64::from(self.sessions_per_era).saturating_mul(self.session_length);
+        if era_length == 0 {
+            return 0
+        }
+        let elapsed = u64::from(self.sessions_into_era)
+            .saturating_mul(self.session_length)
+            .saturating_add(self.slots_into_session);
+        (elapsed.saturating_mul(100) / era_length).min(100)
+    }
+}
+
+/// Read a constant from the chain metadata
+pub(crate) fn constant<T: codec::Decode>(
+    client: &Client<KusamaRuntime>,
+    module: &str,
+    name: &str,
+) -> Result<T, Error> {
+    Ok(client.metadata().module(module)?.constant(name)?.value()?)
+}
+
+/// Fetch the progress of the chain through the current era and session at
+/// block `at`
+pub(crate) async fn era_progress(
+    client: &Client<KusamaRuntime>,
+    at: At<'_>,
+) -> Result<EraProgress, Error> {
+    let session_length: u64 = constant(client, "Babe", "EpochDuration")?;
+    let slot_duration: u64 = constant(client, "Babe", "ExpectedBlockTime")?;
+    let sessions_per_era: u32 = constant(client, "Staking", "SessionsPerEra")?;
+    let (active_era, current_era) = join(
+        fetch(
+            client,
+            &ActiveEraStore {
+                _runtime: PhantomData,
+            },
+            at,
+        ),
+        fetch_or_default(
+            client,
+            &CurrentEraStore {
+                _runtime: PhantomData,
+            },
+            at,
+        ),
+    )
+    .await;
+    let active_era = active_era?.ok_or_else(|| "No active era (this is a bug)".to_owned())?;
+    let (era_start_session, session, (epoch_index, genesis_slot), current_slot) = join4(
+        fetch_or_default(
+            client,
+            &ErasStartSessionIndexStore {
+                era: active_era.index,
+                _runtime: PhantomData,
+            },
+            at,
+        ),
+        fetch_or_default(
+            client,
+            &CurrentIndexStore {
+                _runtime: PhantomData,
+            },
+            at,
+        ),
+        join(
+            fetch_or_default(
+                client,
+                &EpochIndexStore {
+                    _runtime: PhantomData,
+                },
+                at,
+            ),
+            fetch_or_default(
+                client,
+                &GenesisSlotStore {
+                    _runtime: PhantomData,
+                },
+                at,
+            ),
+        ),
+        fetch_or_default(
+            client,
+            &CurrentSlotStore {
+                _runtime: PhantomData,
+            },
+            at,
+        ),
+    )
+    .await;
+    let session = session?;
+    let current_slot = current_slot?;
+    let session_start_slot = epoch_index?
+        .saturating_mul(session_length)
+        .saturating_add(genesis_slot?);
+    Ok(EraProgress {
+        active_era: active_era.index,
+        active_era_start: active_era.start,
+        current_era: current_era?,
+        session,
+        sessions_into_era: session.saturating_sub(era_start_session?),
+        sessions_per_era,
+        slots_into_session: current_slot.saturating_sub(session_start_slot),
+        session_length,
+        slot_duration,
+        now: current_slot.saturating_mul(slot_duration),
+    })
+}
+
+/// Format the progress of the chain through the current era and session
+pub(crate) fn format_era_progress(progress: &EraProgress) -> Result<String, Error> {
+    let mut output = format!("Active era: {}", progress.active_era);
+    if let Some(start) = progress.active_era_start {
+        write!(output, " (started {})", format_timestamp(start))?;
+    }
+    write!(output, "\nCurrent era: {}", progress.current_era)?;
+    if progress.current_era > progress.active_era {
+        output += " (the next validator set has been elected)";
+    }
+    let (until_session, until_era) = (progress.until_next_session(), progress.until_next_era());
+    writeln!(
+        output,
+        "\nSession: {} (session {} of {} in the active era)\nEra progress: {}%\nNext session: in \
+         about {} ({})\nNext era: in about {} ({})",
+        progress.session,
+        progress.sessions_into_era.saturating_add(1),
+        progress.sessions_per_era,
+        progress.percent(),
+        format_duration(until_session),
+        format_timestamp(progress.now.saturating_add(until_session)),
+        format_duration(until_era),
+        format_timestamp(progress.now.saturating_add(until_era)),
+    )?;
+    Ok(output)
+}
+
+/// The progress of the chain through the current era and session, as JSON
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct EraJson {
+    active_era: EraIndex,
+    /// In milliseconds since the Unix epoch, if known
+    active_era_start: Option<u64>,
+    current_era: EraIndex,
+    session: SessionIndex,
+    /// The index of the current session within the active era, starting at 1
+    session_in_era: u32,
+    sessions_per_era: u32,
+    era_progress_percent: u64,
+    /// Estimates, in milliseconds since the Unix epoch
+    next_session_start: u64,
+    next_era_start: u64,
+}
+
+impl From<&EraProgress> for EraJson {
+    fn from(progress: &EraProgress) -> Self {
+        Self {
+            active_era: progress.active_era,
+            active_era_start: progress.active_era_start,
+            current_era: progress.current_era,
+            session: progress.session,
+            session_in_era: progress.sessions_into_era.saturating_add(1),
+            sessions_per_era: progress.sessions_per_era,
+            era_progress_percent: progress.percent(),
+            next_session_start: progress.now.saturating_add(progress.until_next_session()),
+            next_era_start: progress.now.saturating_add(progress.until_next_era()),
+        }
+    }
+}
+
+/// Fetch and format the progress of the chain through the current era and
+/// session at block `at`
+pub(crate) async fn display_era(
+    client: &Client<KusamaRuntime>,
+    at: At<'_>,
+) -> Result<String, Error> {
+    format_era_progress(&era_progress(client, at).await?)
+}
+
+/// Format a duration in milliseconds as days, hours, and minutes, or as
+/// minutes and seconds if it is less than an hour
+pub(crate) fn format_duration(milliseconds: u64) -> String {
+    let seconds = milliseconds / 1000;
+    let (days, hours, minutes) = (seconds / 86400, seconds / 3600 % 24, seconds / 60 % 60);
+    if days > 0 {
+        format!("{}d {}h {}m", days, hours, minutes)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else {
+        format!("{}m {}s", minutes, seconds % 60)
+    }
+}
+
+/// Format a time in milliseconds since the Unix epoch as a UTC date and time
+pub(crate) fn format_timestamp(milliseconds: u64) -> String {
+    let seconds = milliseconds / 1000;
+    let (days, seconds) = (seconds / 86400, seconds % 86400);
+    // Convert days since the epoch to a proleptic Gregorian date.  See
+    // <http://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = era * 400 + year_of_era + u64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds / 60 % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_timestamp_works() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00 UTC");
+        assert_eq!(format_timestamp(951_782_400_000), "2000-02-29 00:00 UTC");
+        assert_eq!(format_timestamp(1_601_554_260_999), "2020-10-01 12:11 UTC");
+    }
+
+    #[test]
+    fn format_duration_works() {
+        assert_eq!(format_duration(59_999), "0m 59s");
+        assert_eq!(format_duration(3_600_000), "1h 0m");
+        assert_eq!(format_duration(90_061_000), "1d 1h 1m");
+    }
+
+    #[test]
+    fn era_progress_works() {
+        let progress = EraProgress {
+            active_era: 10,
+            active_era_start: None,
+            current_era: 10,
+            session: 65,
+            sessions_into_era: 2,
+            sessions_per_era: 6,
+            slots_into_session: 100,
+            session_length: 600,
+            slot_duration: 6000,
+            now: 0,
+        };
+        assert_eq!(progress.until_next_session(), 500 * 6000);
+        assert_eq!(progress.until_next_era(), (3 * 600 + 500) * 6000);
+        assert_eq!(progress.percent(), 1300 * 100 / 3600);
+        assert_eq!(progress.era_start(10), None);
+        assert_eq!(progress.era_start(11), Some(progress.until_next_era()));
+        assert_eq!(
+            progress.era_start(13),
+            Some(progress.until_next_era() + 2 * 6 * 600 * 6000)
+        );
+        assert_eq!(progress.unlocks_at(11), progress.era_start(11));
+        // Once the next era is planned, funds unlocking in it can be withdrawn.
+        let planned = EraProgress {
+            current_era: 11,
+            ..progress
+        };
+        assert!(planned.unlocked(11));
+        assert_eq!(planned.unlocks_at(11), None);
+        assert!(!planned.unlocked(12));
+    }
+}
@@ -0,0 +1,48 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of ledgeracio.
+//
+// ledgeracio is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// ledgeracio is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ledgeracio.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Storage items of the I’m Online pallet, which `substrate_subxt` does not
+//! provide
+
+use crate::session::SessionIndex;
+use codec::{Decode, Encode};
+use std::marker::PhantomData;
+use substrate_subxt::{session::Session, Store};
+
+const MODULE: &str = "ImOnline";
+
+/// The heartbeat received from a validator in a given session, keyed by
+/// session and by the index of the validator in the session’s validator set
+#[derive(Clone, Encode, Decode, Debug, Store)]
+pub struct ReceivedHeartbeatsStore<T: Session> {
+    #[store(returns = Vec<u8>)]
+    /// The session
+    pub session: SessionIndex,
+    /// The index of the validator in the validator set of the session
+    pub authority_index: u32,
+    /// Runtime marker
+    pub _runtime: PhantomData<T>,
+}
+
+/// The number of blocks authored by a validator in a given session
+#[derive(Clone, Encode, Decode, Debug, Store)]
+pub struct AuthoredBlocksStore<T: Session> {
+    #[store(returns = u32)]
+    /// The session
+    pub session: SessionIndex,
+    /// The validator
+    pub validator_id: T::ValidatorId,
+}
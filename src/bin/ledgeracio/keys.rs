@@ -0,0 +1,62 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of ledgeracio.
+//
+// ledgeracio is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// ledgeracio is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ledgeracio.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Session key handling
+
+use codec::Encode;
+use substrate_subxt::SessionKeys;
+
+/// A single session key
+pub(crate) struct SessionKey {
+    /// The human-readable name of the key
+    pub(crate) name: &'static str,
+    /// The key type identifier used by the node’s keystore
+    pub(crate) key_type: &'static str,
+    /// The public key
+    pub(crate) public: Vec<u8>,
+}
+
+/// Split session keys into the individual keys, in the order in which they
+/// are encoded
+pub(crate) fn session_keys(keys: &SessionKeys) -> Vec<SessionKey> {
+    let key = |name, key_type, public| SessionKey {
+        name,
+        key_type,
+        public,
+    };
+    vec![
+        key("grandpa", "gran", keys.grandpa.encode()),
+        key("babe", "babe", keys.babe.encode()),
+        key("im-online", "imon", keys.im_online.encode()),
+        key("parachain", "para", keys.parachain_validator.encode()),
+        key(
+            "authority discovery",
+            "audi",
+            keys.authority_discovery.encode(),
+        ),
+    ]
+}
+
+/// Format a public key as hexadecimal
+pub(crate) fn format_key(public: &[u8]) -> String { format!("0x{}", hex::encode(public)) }
+
+/// Format session keys, one key per line, with each line prefixed by `indent`
+pub(crate) fn format_session_keys(keys: &SessionKeys, indent: &str) -> String {
+    session_keys(keys)
+        .into_iter()
+        .map(|key| format!("{}{}: {}\n", indent, key.name, format_key(&key.public)))
+        .collect()
+}
@@ -0,0 +1,252 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of ledgeracio.
+//
+// ledgeracio is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// ledgeracio is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ledgeracio.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The storage layout of the items that can be read with `--verify-proofs`.
+//! Normally the storage keys and default values come from the metadata, which
+//! the node can forge, so for verified reads they are fixed here instead.
+
+use self::{Hasher::{Blake2_128Concat, Twox64Concat},
+           Kind::{DoubleMap, Map, Plain}};
+use crate::Error;
+use codec::{Decode, Encode};
+use substrate_subxt::{sp_core::{blake2_128, storage::StorageKey, twox_128, twox_64},
+                      KusamaRuntime, Metadata, Store};
+
+/// A hasher for the keys of a map
+#[derive(Clone, Copy)]
+enum Hasher {
+    Twox64Concat,
+    Blake2_128Concat,
+}
+
+impl Hasher {
+    /// Hash the encoded key `key`.  Both hashers append the key itself.
+    fn hash(self, key: &[u8]) -> Vec<u8> {
+        let mut hash = match self {
+            Twox64Concat => twox_64(key).to_vec(),
+            Blake2_128Concat => blake2_128(key).to_vec(),
+        };
+        hash.extend_from_slice(key);
+        hash
+    }
+}
+
+/// How the key of a storage item is built from its encoded arguments
+#[derive(Clone, Copy)]
+enum Kind {
+    Plain,
+    Map(Hasher),
+    /// A double map, whose first key is always encoded in the given number of
+    /// bytes
+    DoubleMap(Hasher, usize, Hasher),
+}
+
+/// The layout of a storage item
+struct Layout {
+    module: &'static str,
+    field: &'static str,
+    kind: Kind,
+    /// The encoded default value, or `None` if the item is optional
+    default: Option<&'static [u8]>,
+}
+
+/// Long enough to decode as the all-zero default value of any item below
+const ZERO: &[u8] = &[0; 128];
+
+/// A double map whose first key is a `u32`, such as an era or session index
+const BY_INDEX: Kind = DoubleMap(Twox64Concat, 4, Twox64Concat);
+
+/// The `HistoryDepth` of a new chain
+const HISTORY_DEPTH: &[u8] = &[84, 0, 0, 0];
+
+const fn item(module: &'static str, field: &'static str, kind: Kind) -> Layout {
+    Layout {
+        module,
+        field,
+        kind,
+        default: None,
+    }
+}
+
+const fn value(
+    module: &'static str,
+    field: &'static str,
+    kind: Kind,
+    default: &'static [u8],
+) -> Layout {
+    Layout {
+        module,
+        field,
+        kind,
+        default: Some(default),
+    }
+}
+
+/// Every item that can be read with `--verify-proofs`, as in the Kusama and
+/// Polkadot runtimes
+const LAYOUTS: &[Layout] = &[
+    value("System", "Account", Map(Blake2_128Concat), ZERO),
+    value("Staking", "HistoryDepth", Plain, HISTORY_DEPTH),
+    item("Staking", "Bonded", Map(Twox64Concat)),
+    item("Staking", "Ledger", Map(Blake2_128Concat)),
+    value("Staking", "Payee", Map(Twox64Concat), ZERO),
+    value("Staking", "Validators", Map(Twox64Concat), ZERO),
+    item("Staking", "Nominators", Map(Twox64Concat)),
+    item("Staking", "CurrentEra", Plain),
+    item("Staking", "ActiveEra", Plain),
+    item("Staking", "ErasStartSessionIndex", Map(Twox64Concat)),
+    value("Staking", "ErasStakers", BY_INDEX, ZERO),
+    value("Staking", "ErasStakersClipped", BY_INDEX, ZERO),
+    value("Staking", "ErasValidatorPrefs", BY_INDEX, ZERO),
+    item("Staking", "ErasValidatorReward", Map(Twox64Concat)),
+    value("Staking", "ErasRewardPoints", Map(Twox64Concat), ZERO),
+    value("Staking", "UnappliedSlashes", Map(Twox64Concat), ZERO),
+    item("Staking", "SlashingSpans", Map(Twox64Concat)),
+    value("Staking", "MinCommission", Plain, ZERO),
+    value("Session", "Validators", Plain, ZERO),
+    value("Session", "CurrentIndex", Plain, ZERO),
+    value("Session", "QueuedKeys", Plain, ZERO),
+    item("Session", "NextKeys", Map(Twox64Concat)),
+    value("Babe", "EpochIndex", Plain, ZERO),
+    value("Babe", "GenesisSlot", Plain, ZERO),
+    value("Babe", "CurrentSlot", Plain, ZERO),
+    item("ImOnline", "ReceivedHeartbeats", BY_INDEX),
+    value("ImOnline", "AuthoredBlocks", BY_INDEX, ZERO),
+];
+
+fn layout<F: Store<KusamaRuntime>>() -> Result<&'static Layout, Error> {
+    LAYOUTS
+        .iter()
+        .find(|layout| layout.module == F::MODULE && layout.field == F::FIELD)
+        .ok_or_else(|| {
+            format!(
+                "{}.{} cannot be read with --verify-proofs",
+                F::MODULE,
+                F::FIELD
+            )
+            .into()
+        })
+}
+
+/// The storage key of `store`, built without the metadata
+fn build_key<F: Store<KusamaRuntime>>(store: &F) -> Result<Vec<u8>, Error> {
+    let layout = layout::<F>()?;
+    let mut key = [
+        twox_128(layout.module.as_bytes()),
+        twox_128(layout.field.as_bytes()),
+    ]
+    .concat();
+    let arguments = store.encode();
+    match layout.kind {
+        Plain => (),
+        Map(hasher) => key.extend(hasher.hash(&arguments)),
+        DoubleMap(first, length, second) => {
+            if arguments.len() < length {
+                return Err(format!("Bad key for {}.{} (this is a bug)", F::MODULE, F::FIELD).into())
+            }
+            let (key1, key2) = arguments.split_at(length);
+            key.extend(first.hash(key1));
+            key.extend(second.hash(key2))
+        }
+    }
+    Ok(key)
+}
+
+/// The storage key of `store`, built without the metadata.  The key is also
+/// built from `metadata`, and if the two differ (because the runtime has
+/// changed, or the node is lying) this fails rather than read another item.
+pub(crate) fn key<F: Store<KusamaRuntime>>(
+    store: &F,
+    metadata: &Metadata,
+) -> Result<StorageKey, Error> {
+    let key = build_key(store)?;
+    if store.key(metadata).ok().map(|key| key.0) != Some(key.clone()) {
+        return Err(format!(
+            "The storage layout of {}.{} in the node’s metadata is not the expected one, so it \
+             cannot be read with --verify-proofs",
+            F::MODULE,
+            F::FIELD
+        )
+        .into())
+    }
+    Ok(StorageKey(key))
+}
+
+/// The default value of the item read by `F`, without the metadata
+pub(crate) fn default<F: Store<KusamaRuntime>>() -> Result<F::Returns, Error> {
+    let default = layout::<F>()?
+        .default
+        .ok_or_else(|| format!("{}.{} has no default value", F::MODULE, F::FIELD))?;
+    Ok(Decode::decode(&mut &*default)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::marker::PhantomData;
+    use substrate_subxt::{sp_core::crypto::AccountId32 as AccountId,
+                          staking::{CurrentEraStore, HistoryDepthStore, LedgerStore, PayeeStore,
+                                    RewardDestination},
+                          system::AccountStore};
+
+    #[test]
+    fn keys_match_the_chain() {
+        let current_era = CurrentEraStore::<KusamaRuntime> {
+            _runtime: PhantomData,
+        };
+        assert_eq!(
+            hex::encode(build_key(&current_era).unwrap()),
+            "5f3e4907f716ac89b6347d15ececedca0b6a45321efae92aea15e0740ec7afe7"
+        );
+        let account_id = AccountId::from([7; 32]);
+        let key = build_key(&AccountStore::<KusamaRuntime> {
+            account_id: &account_id,
+        })
+        .unwrap();
+        assert_eq!(
+            hex::encode(&key[..32]),
+            "26aa394eea5630e07c48ae0c9558cef7b99d880ec681799c0cf30e8886371da9"
+        );
+        assert_eq!(key[32..48], blake2_128(&[7; 32]));
+        assert_eq!(key[48..], [7; 32]);
+    }
+
+    #[test]
+    fn hashers_append_the_key() {
+        let key = [1, 2, 3, 4];
+        assert_eq!(Twox64Concat.hash(&key)[8..], key);
+        assert_eq!(Blake2_128Concat.hash(&key)[16..], key);
+    }
+
+    #[test]
+    fn defaults_decode() {
+        assert_eq!(default::<HistoryDepthStore<KusamaRuntime>>().unwrap(), 84);
+        assert!(matches!(
+            default::<PayeeStore<KusamaRuntime>>().unwrap(),
+            RewardDestination::Staked
+        ));
+        assert!(default::<LedgerStore<KusamaRuntime>>().is_err());
+    }
+
+    #[test]
+    fn every_layout_is_unique() {
+        for (index, layout) in LAYOUTS.iter().enumerate() {
+            assert!(!LAYOUTS[..index]
+                .iter()
+                .any(|other| other.module == layout.module && other.field == layout.field));
+        }
+    }
+}
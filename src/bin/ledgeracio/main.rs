@@ -20,10 +20,28 @@
 #![allow(clippy::non_ascii_literal)]
 #![forbid(unsafe_code)]
 
+mod babe;
 mod common;
+mod doctor;
+mod era;
+mod im_online;
+mod keys;
+mod layout;
+mod metadata;
+mod metrics;
+mod monitor;
 mod nominator;
+mod offences;
+mod output;
 mod payouts;
+mod proof;
+mod rewards;
+mod rpc;
+mod session;
+mod staking;
+mod utility;
 mod validator;
+mod watch;
 
 use futures::future::TryFutureExt;
 use ledgeracio::{get_network, AccountType, Error, HardSigner, HardStore, LedgeracioPath};
@@ -32,8 +50,9 @@ use ledgeracio::{get_network, AccountType, Error, HardSigner, HardStore, Ledgera
 compile_error!("Only *nix-like platforms are supported");
 
 use common::AddressSource;
+use output::OutputFormat;
 use sp_core::crypto::AccountId32 as AccountId;
-use std::{fmt::Debug, future::Future, pin::Pin};
+use std::{convert::TryInto, fmt::Debug, future::Future, path::PathBuf, pin::Pin};
 use structopt::StructOpt;
 use substrate_subxt::{sp_core,
                       sp_core::crypto::{Ss58AddressFormat, Ss58Codec},
@@ -52,6 +71,31 @@ struct Ledgeracio {
     /// Network
     #[structopt(long, parse(try_from_str = get_network))]
     network: Ss58AddressFormat,
+    /// `text` or `json`.  JSON output is supported by the commands that only
+    /// read from the chain, and its schemas are documented in FORMATS.md.
+    /// Errors are then reported as JSON on stderr.
+    #[structopt(long, default_value = "text")]
+    output: OutputFormat,
+    /// Read the chain state at this block, given by number or by hash,
+    /// instead of at the best block.  Only supported by the commands that
+    /// only read from the chain.
+    #[structopt(long)]
+    at: Option<rpc::BlockId>,
+    /// Check everything read from the chain state against storage proofs,
+    /// at blocks proven to be finalized starting from a trusted checkpoint.
+    /// Without `--at`, the state is read at the last finalized block whose
+    /// justification the node keeps.
+    #[structopt(long)]
+    verify_proofs: bool,
+    /// With `--verify-proofs`, the most blocks that the state read by a
+    /// command that submits a transaction may be behind the finalized head.
+    /// Only every 512th block can be verified, so the state is often older.
+    #[structopt(long, default_value = "100")]
+    max_lag: u32,
+    /// The checkpoint file used by `--verify-proofs`.  The default is
+    /// `ledgeracio/<network>-checkpoint.json` in the configuration directory.
+    #[structopt(long, parse(from_os_str))]
+    checkpoint: Option<PathBuf>,
     /// Subcommand
     #[structopt(subcommand)]
     cmd: Command,
@@ -62,6 +106,7 @@ async fn display_path(
     keystore: &HardStore,
     network: Ss58AddressFormat,
     index: u32,
+    output: OutputFormat,
 ) -> Result<(), Error> {
     if index == 0 {
         return Err("Index must not be zero".to_owned().into())
@@ -69,7 +114,11 @@ async fn display_path(
     let path = LedgeracioPath::new(network, account_type, index)?;
     let signer: HardSigner = keystore.signer(path).await?;
     let account_id: &AccountId = signer.account_id();
-    println!("{}", account_id.to_ss58check_with_version(network));
+    let address = account_id.to_ss58check_with_version(network);
+    match output {
+        OutputFormat::Json => output::print_json(&serde_json::json!({ "address": address }))?,
+        OutputFormat::Text => println!("{}", address),
+    }
     Ok(())
 }
 
@@ -79,35 +128,190 @@ enum Command {
     Nominator(nominator::Nominator),
     /// Validator operations
     Validator(validator::Validator),
-    /// Pretty-print the chain metadata
-    Metadata,
+    /// Reward history
+    Rewards(rewards::Rewards),
+    /// Inspect the chain metadata
+    Metadata(metadata::Metadata),
     /// Display the chain properties
     Properties,
+    /// Display the progress of the current era and session
+    Era,
+    /// Check every account on the device for common problems
+    Doctor,
+    /// Serve staking metrics of a list of stashes to Prometheus
+    ServeMetrics(metrics::ServeMetrics),
+    /// Run hooks when events concerning a set of stashes happen
+    Monitor(monitor::Monitor),
+    /// Trust the finalized block with the given hash, and save its GRANDPA
+    /// authorities as the checkpoint for `--verify-proofs`.  The hash must
+    /// come from a source you trust, such as your own node.
+    Checkpoint {
+        #[structopt(parse(try_from_str = parse_block_hash))]
+        hash: sp_core::H256,
+    },
+}
+
+impl Command {
+    /// Whether the command supports `--output json`
+    fn supports_json(&self) -> bool {
+        match self {
+            Self::Nominator(s) => s.supports_json(),
+            Self::Validator(v) => v.supports_json(),
+            Self::Rewards(_) | Self::Metadata(_) | Self::Properties | Self::Era | Self::Doctor => {
+                true
+            }
+            Self::ServeMetrics(_) | Self::Monitor(_) | Self::Checkpoint { .. } => false,
+        }
+    }
+
+    /// Whether the command supports `--verify-proofs`
+    fn supports_verify(&self) -> bool {
+        match self {
+            Self::Nominator(s) => s.supports_verify(),
+            Self::Validator(v) => v.supports_verify(),
+            Self::Rewards(_) | Self::Era | Self::Doctor => true,
+            Self::Metadata(_)
+            | Self::Properties
+            | Self::ServeMetrics(_)
+            | Self::Monitor(_)
+            | Self::Checkpoint { .. } => false,
+        }
+    }
+
+    /// Whether the command signs and submits a transaction
+    fn submits(&self) -> bool {
+        match self {
+            Self::Nominator(s) => s.submits(),
+            Self::Validator(v) => v.submits(),
+            Self::Rewards(_)
+            | Self::Era
+            | Self::Doctor
+            | Self::Metadata(_)
+            | Self::Properties
+            | Self::ServeMetrics(_)
+            | Self::Monitor(_)
+            | Self::Checkpoint { .. } => false,
+        }
+    }
+
+    /// Whether the command supports `--at`
+    fn supports_at(&self) -> bool {
+        match self {
+            Self::Nominator(s) => s.supports_at(),
+            Self::Validator(v) => v.supports_at(),
+            Self::Metadata(m) => m.supports_at(),
+            Self::Rewards(_) | Self::Era | Self::Doctor => true,
+            Self::Properties
+            | Self::ServeMetrics(_)
+            | Self::Monitor(_)
+            | Self::Checkpoint { .. } => false,
+        }
+    }
 }
 
 type Runtime = substrate_subxt::KusamaRuntime;
 
-fn parse_reward_destination(arg: &str) -> Result<RewardDestination<AccountId>, Error> {
-    Ok(match &*arg.to_ascii_lowercase() {
+/// Parse a reward destination.  The network of an `account:` destination is
+/// returned unchecked, and must be checked with
+/// [`validate_reward_destination`].
+fn parse_reward_destination(arg: &str) -> Result<RewardDestination<(AccountId, u8)>, Error> {
+    let lowercase = arg.to_ascii_lowercase();
+    Ok(match &*lowercase {
         "staked" => RewardDestination::Staked,
         "stash" => RewardDestination::Stash,
         "controller" => RewardDestination::Controller,
+        "none" => RewardDestination::None,
+        _ if lowercase.starts_with("account:") => {
+            RewardDestination::Account(ledgeracio::parse_address(&arg["account:".len()..])?)
+        }
         _ => {
-            return Err("Arbitrary reward destinations not supported"
-                .to_owned()
-                .into())
+            return Err(format!(
+                "Invalid reward destination {}: expected `staked`, `stash`, `controller`, `none`, \
+                 or `account:<address>`",
+                arg
+            )
+            .into())
         }
     })
 }
 
-async fn inner_main() -> Result<(), Error> {
+/// Parse a block hash, which is `0x`-prefixed hex
+fn parse_block_hash(arg: &str) -> Result<sp_core::H256, String> {
+    match arg.parse::<rpc::BlockId>()? {
+        rpc::BlockId::Hash(hash) => Ok(hash),
+        rpc::BlockId::Number(_) => Err(format!(
+            "Invalid block hash {}: expected 0x-prefixed hex, not a block number",
+            arg
+        )),
+    }
+}
+
+/// Check that the address (if any) in a reward destination is for `network`.
+fn validate_reward_destination(
+    destination: RewardDestination<(AccountId, u8)>,
+    network: Ss58AddressFormat,
+) -> Result<RewardDestination<AccountId>, Error> {
+    Ok(match destination {
+        RewardDestination::Staked => RewardDestination::Staked,
+        RewardDestination::Stash => RewardDestination::Stash,
+        RewardDestination::Controller => RewardDestination::Controller,
+        RewardDestination::None => RewardDestination::None,
+        RewardDestination::Account((account, provided_network)) => {
+            ledgeracio::validate_network(
+                &account.to_ss58check_with_version(provided_network.try_into().unwrap()),
+                provided_network,
+                network,
+            )?;
+            RewardDestination::Account(account)
+        }
+    })
+}
+
+/// Report how old the verified state is before it is used to build a
+/// transaction, and fail if it is more than `max_lag` blocks behind the
+/// finalized head
+fn check_lag(verifier: &proof::Verifier, max_lag: u32) -> Result<(), Error> {
+    let (number, lag) = verifier.lag();
+    // Polkadot and Kusama produce a block every 6 seconds.
+    eprintln!(
+        "Reading the chain state at verified block #{}, {} blocks (about {} minutes) behind the \
+         finalized head",
+        number,
+        lag,
+        lag / 10
+    );
+    if lag > max_lag {
+        return Err(format!(
+            "The verified state is more than {} blocks old.  Pass --max-lag {} to use it anyway.",
+            max_lag, lag
+        )
+        .into())
+    }
+    Ok(())
+}
+
+async fn inner_main(args: Ledgeracio) -> Result<(), Error> {
     env_logger::init();
     let Ledgeracio {
         dry_run,
         host,
         network,
+        output,
+        at,
+        verify_proofs,
+        max_lag,
+        checkpoint,
         cmd,
-    } = Ledgeracio::from_args();
+    } = args;
+    if output == OutputFormat::Json && !cmd.supports_json() {
+        return Err("This command does not support --output json".into())
+    }
+    if at.is_some() && !cmd.supports_at() {
+        return Err("This command does not support --at".into())
+    }
+    if verify_proofs && !cmd.supports_verify() {
+        return Err("This command does not support --verify-proofs".into())
+    }
     let host = match (host, network) {
         (Some(host), _) => host,
         (None, Ss58AddressFormat::KusamaAccount) => "wss://kusama-rpc.polkadot.io".into(),
@@ -115,24 +319,84 @@ async fn inner_main() -> Result<(), Error> {
         _ => return Err("Please supply an RPC endpoint".into()),
     };
 
-    let client = ClientBuilder::<Runtime>::new()
-        .set_url(host)
-        .build()
-        .map_err(From::from);
-    let client: Pin<Box<dyn Future<Output = Result<Client<Runtime>, _>>>> = Box::pin(client);
     let keystore = || HardStore::new(network);
     if dry_run {
         return Ok(())
     }
+    // The client shares this connection, so that reads subxt does not support
+    // go to the same node.
+    let rpc = rpc::connect(&host).await?;
+    let client = ClientBuilder::<Runtime>::new()
+        .set_client(rpc.clone())
+        .build()
+        .map_err(From::from);
+    let client: Pin<Box<dyn Future<Output = Result<Client<Runtime>, _>>>> = Box::pin(client);
+    let verifier = if verify_proofs {
+        let path = proof::checkpoint_path(checkpoint.as_deref(), network)?;
+        Some(proof::Verifier::new(&host, &path).await?)
+    } else {
+        None
+    };
+    let at = match (at, &verifier) {
+        (Some(block), Some(verifier)) => Some(verifier.block_hash(block)?),
+        (Some(block), None) => Some(rpc::block_hash(&rpc, block).await?),
+        (None, Some(verifier)) => {
+            if cmd.submits() {
+                check_lag(verifier, max_lag)?
+            }
+            Some(verifier.latest())
+        }
+        (None, None) => None,
+    };
+    let at = common::At {
+        hash: at,
+        verifier: verifier.as_ref(),
+        rpc: &rpc,
+    };
     if let Some(hash) = match cmd {
-        Command::Nominator(s) => nominator::main(s, client, network, keystore).await?,
-        Command::Validator(v) => validator::main(v, client, network, keystore).await?,
-        Command::Metadata => {
-            println!("{:#?}", client.await?.metadata());
+        Command::Nominator(s) => nominator::main(s, client, network, keystore, output, at).await?,
+        Command::Validator(v) => validator::main(v, client, network, keystore, output, at).await?,
+        Command::Rewards(r) => rewards::main(r, client, network, keystore, output, at).await?,
+        Command::Metadata(m) => {
+            metadata::main(m, &rpc, output, at.hash).await?;
             None
         }
         Command::Properties => {
-            println!("{:#?}", client.await?.properties());
+            let client = client.await?;
+            let properties = client.properties();
+            match output {
+                OutputFormat::Json => output::print_json(&serde_json::json!({
+                    "ss58_format": properties.ss58_format,
+                    "token_decimals": properties.token_decimals,
+                    "token_symbol": properties.token_symbol,
+                }))?,
+                OutputFormat::Text => println!("{:#?}", properties),
+            }
+            None
+        }
+        Command::Era => {
+            let progress = era::era_progress(&client.await?, at).await?;
+            match output {
+                OutputFormat::Json => output::print_json(&era::EraJson::from(&progress))?,
+                OutputFormat::Text => print!("{}", era::format_era_progress(&progress)?),
+            }
+            None
+        }
+        Command::Doctor => {
+            doctor::main(&client.await?, &keystore()?, network, output, at).await?;
+            None
+        }
+        Command::ServeMetrics(m) => {
+            metrics::main(m, &client.await?, &rpc, network).await?;
+            None
+        }
+        Command::Monitor(m) => {
+            monitor::main(m, &client.await?, &rpc, network).await?;
+            None
+        }
+        Command::Checkpoint { hash } => {
+            let path = proof::checkpoint_path(checkpoint.as_deref(), network)?;
+            proof::create_checkpoint(&host, hash, &path).await?;
             None
         }
     } {
@@ -142,10 +406,15 @@ async fn inner_main() -> Result<(), Error> {
 }
 
 fn main() {
-    match async_std::task::block_on(inner_main()) {
+    let args = Ledgeracio::from_args();
+    let output = args.output;
+    match async_std::task::block_on(inner_main(args)) {
         Ok(()) => (),
         Err(e) => {
-            eprintln!("Error: {}", e);
+            match output {
+                OutputFormat::Json => eprintln!("{}", output::error_json(&e.to_string())),
+                OutputFormat::Text => eprintln!("Error: {}", e),
+            }
             std::process::exit(1)
         }
     }
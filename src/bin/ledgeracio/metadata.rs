@@ -0,0 +1,589 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of ledgeracio.
+//
+// ledgeracio is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// ledgeracio is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ledgeracio.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Metadata commands: list the pallets of the runtime, show the calls,
+//! storage, constants, events, and errors of a pallet, and compare saved
+//! metadata
+
+use super::{Error, StructOpt};
+use crate::{common::format_commission,
+            output::{print_json, OutputFormat}};
+use codec::Decode;
+use frame_metadata::{DecodeDifferent, RuntimeMetadata, RuntimeMetadataPrefixed,
+                     StorageEntryModifier, StorageEntryType, StorageHasher, META_RESERVED};
+use ledgeracio::pad;
+use serde::Serialize;
+use std::{collections::BTreeMap,
+          convert::TryFrom,
+          fmt::Write as _,
+          fs,
+          path::{Path, PathBuf}};
+use substrate_subxt::{sp_core::H256, sp_runtime::Perbill};
+
+#[derive(StructOpt, Debug)]
+pub(crate) enum Metadata {
+    /// List the pallets of the runtime
+    Pallets,
+    /// Show the calls, storage items, constants, events, and errors of a
+    /// pallet
+    Pallet { name: String },
+    /// Save the metadata of the runtime to a file, for use with `diff`
+    Save { file: PathBuf },
+    /// Compare two saved metadata files.  This does not require a network
+    /// connection.
+    Diff { old: PathBuf, new: PathBuf },
+}
+
+impl Metadata {
+    /// Whether the command supports `--at`
+    pub(crate) fn supports_at(&self) -> bool {
+        match self {
+            Self::Pallets | Self::Pallet { .. } | Self::Save { .. } => true,
+            Self::Diff { .. } => false,
+        }
+    }
+}
+
+/// A pallet of the runtime
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Pallet {
+    name: String,
+    /// The index of the pallet, which is part of the encoding of its calls
+    index: u8,
+    calls: Vec<Item>,
+    storage: Vec<Item>,
+    constants: Vec<Item>,
+    events: Vec<Item>,
+    errors: Vec<Item>,
+}
+
+/// A call, storage item, constant, event, or error of a pallet
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+struct Item {
+    name: String,
+    /// The position of a call, event, or error in its pallet, which is part
+    /// of its encoding
+    index: Option<u8>,
+    /// The arguments of a call or event, such as `(who: T::AccountId)`, or
+    /// the type of a storage item or constant.  Errors have none.
+    signature: Option<String>,
+    /// The decoded value of a constant, or the decoded default value of a
+    /// storage item that has one
+    value: Option<String>,
+    docs: Vec<String>,
+}
+
+impl Item {
+    /// Format the item as a single line, without its documentation
+    fn format(&self) -> String {
+        match (&self.signature, &self.value) {
+            (Some(signature), Some(value)) => format!("{}: {} = {}", self.name, signature, value),
+            (Some(signature), None) if signature.starts_with('(') => {
+                format!("{}{}", self.name, signature)
+            }
+            (Some(signature), None) => format!("{}: {}", self.name, signature),
+            (None, _) => self.name.clone(),
+        }
+    }
+
+    /// Format the item as a single line, along with its index if it has one
+    fn describe(&self) -> String {
+        match self.index {
+            Some(index) => format!("{} (index {})", self.format(), index),
+            None => self.format(),
+        }
+    }
+}
+
+/// The index of the item at `position` in a list of calls, events, or errors
+fn item_index(position: usize) -> Result<Option<u8>, Error> {
+    match u8::try_from(position) {
+        Ok(index) => Ok(Some(index)),
+        Err(_) => Err(format!("Too many items in a pallet: {}", position + 1).into()),
+    }
+}
+
+/// A difference between two versions of the metadata
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Change {
+    pallet: String,
+    /// `pallet`, `calls`, `storage`, `constants`, `events`, or `errors`
+    kind: &'static str,
+    name: String,
+    /// The old version, or `null` if it was added
+    old: Option<String>,
+    /// The new version, or `null` if it was removed
+    new: Option<String>,
+}
+
+/// Extract a decoded value.  Decoded metadata never contains the `Encode`
+/// variant, which only exists so that the runtime can build its metadata
+/// without allocating.
+fn decoded<B, O>(value: DecodeDifferent<B, O>) -> Result<O, Error> {
+    match value {
+        DecodeDifferent::Decoded(value) => Ok(value),
+        DecodeDifferent::Encode(_) => Err("Metadata is not decoded (this is a bug)".into()),
+    }
+}
+
+/// Decode `value` as a `T`, failing if any bytes are left over
+fn decode_exact<T: Decode>(mut value: &[u8]) -> Option<T> {
+    let decoded = T::decode(&mut value).ok()?;
+    if value.is_empty() {
+        Some(decoded)
+    } else {
+        None
+    }
+}
+
+/// Decode the value of a constant of type `ty`.  Types that are not known are
+/// shown as hex.
+fn decode_constant(ty: &str, value: &[u8]) -> String {
+    let decoded = match ty {
+        "bool" => decode_exact::<bool>(value).map(|v| v.to_string()),
+        "u8" => decode_exact::<u8>(value).map(|v| v.to_string()),
+        "u16" => decode_exact::<u16>(value).map(|v| v.to_string()),
+        "u32" | "T::BlockNumber" | "BlockNumber" | "EraIndex" | "SessionIndex" => {
+            decode_exact::<u32>(value).map(|v| v.to_string())
+        }
+        "u64" | "T::Moment" | "Moment" | "MomentOf<T>" | "Weight" | "TransactionPriority" => {
+            decode_exact::<u64>(value).map(|v| v.to_string())
+        }
+        "u128" | "Balance" | "BalanceOf<T>" | "BalanceOf<T, I>" | "T::Balance" => {
+            decode_exact::<u128>(value).map(|v| v.to_string())
+        }
+        "Perbill" => decode_exact::<u32>(value).map(|v| format_commission(Perbill::from_parts(v))),
+        "Permill" => {
+            decode_exact::<u32>(value).map(|v| format!("{}%", pad(6, u128::from(v) * 100)))
+        }
+        "Percent" => decode_exact::<u8>(value).map(|v| format!("{}%", v)),
+        _ => None,
+    };
+    decoded.unwrap_or_else(|| format!("0x{}", hex::encode(value)))
+}
+
+/// Format a storage key of a map with its hasher, such as
+/// `Twox64Concat(T::AccountId)`
+fn format_key(hasher: &StorageHasher, key: &str) -> String { format!("{:?}({})", hasher, key) }
+
+/// Format the type of a storage item, such as
+/// `map Twox64Concat(T::AccountId) => Option<StakingLedger>`.  The type of
+/// its value, without `Option`, is returned as well.
+fn format_storage_type(
+    ty: StorageEntryType,
+    modifier: &StorageEntryModifier,
+) -> Result<(String, String), Error> {
+    let (keys, value) = match ty {
+        StorageEntryType::Plain(value) => (String::new(), decoded(value)?),
+        StorageEntryType::Map {
+            hasher, key, value, ..
+        } => (
+            format!("map {} => ", format_key(&hasher, &decoded(key)?)),
+            decoded(value)?,
+        ),
+        StorageEntryType::DoubleMap {
+            hasher,
+            key1,
+            key2,
+            value,
+            key2_hasher,
+        } => (
+            format!(
+                "double map {}, {} => ",
+                format_key(&hasher, &decoded(key1)?),
+                format_key(&key2_hasher, &decoded(key2)?)
+            ),
+            decoded(value)?,
+        ),
+    };
+    let signature = match modifier {
+        StorageEntryModifier::Optional => format!("{}Option<{}>", keys, value),
+        StorageEntryModifier::Default => format!("{}{}", keys, value),
+    };
+    Ok((signature, value))
+}
+
+/// Format the arguments of a call, such as `(who: T::AccountId)`
+fn format_arguments<I: IntoIterator<Item = (String, String)>>(arguments: I) -> String {
+    let arguments: Vec<_> = arguments
+        .into_iter()
+        .map(|(name, ty)| format!("{}: {}", name, ty))
+        .collect();
+    format!("({})", arguments.join(", "))
+}
+
+/// Decode the pallets from SCALE-encoded metadata, in the order of their
+/// indices
+pub(crate) fn pallets(metadata: &[u8]) -> Result<Vec<Pallet>, Error> {
+    let RuntimeMetadataPrefixed(magic, metadata) = decode_exact(metadata)
+        .ok_or("Could not decode the metadata: only version 12 is supported")?;
+    if magic != META_RESERVED {
+        return Err("Not runtime metadata: bad magic number".into())
+    }
+    let modules = match metadata {
+        RuntimeMetadata::V12(metadata) => decoded(metadata.modules)?,
+        _ => return Err("Unsupported metadata version: only version 12 is supported".into()),
+    };
+    let mut pallets = vec![];
+    for module in modules {
+        let mut storage = vec![];
+        if let Some(metadata) = module.storage {
+            for entry in decoded(decoded(metadata)?.entries)? {
+                let (signature, ty) = format_storage_type(entry.ty, &entry.modifier)?;
+                // Optional items default to `None`, which is not shown.
+                let value = match entry.modifier {
+                    StorageEntryModifier::Optional => None,
+                    StorageEntryModifier::Default => {
+                        Some(decode_constant(&ty, &decoded(entry.default)?))
+                    }
+                };
+                storage.push(Item {
+                    name: decoded(entry.name)?,
+                    index: None,
+                    signature: Some(signature),
+                    value,
+                    docs: decoded(entry.documentation)?,
+                })
+            }
+        }
+        let mut calls = vec![];
+        let module_calls = decoded(module.calls.unwrap_or(DecodeDifferent::Decoded(vec![])))?;
+        for (position, call) in module_calls.into_iter().enumerate() {
+            let arguments = decoded(call.arguments)?
+                .into_iter()
+                .map(|argument| Ok((decoded(argument.name)?, decoded(argument.ty)?)))
+                .collect::<Result<Vec<_>, Error>>()?;
+            calls.push(Item {
+                name: decoded(call.name)?,
+                index: item_index(position)?,
+                signature: Some(format_arguments(arguments)),
+                value: None,
+                docs: decoded(call.documentation)?,
+            })
+        }
+        let mut events = vec![];
+        let module_events = decoded(module.event.unwrap_or(DecodeDifferent::Decoded(vec![])))?;
+        for (position, event) in module_events.into_iter().enumerate() {
+            events.push(Item {
+                name: decoded(event.name)?,
+                index: item_index(position)?,
+                signature: Some(format!("({})", decoded(event.arguments)?.join(", "))),
+                value: None,
+                docs: decoded(event.documentation)?,
+            })
+        }
+        let mut constants = vec![];
+        for constant in decoded(module.constants)? {
+            let ty = decoded(constant.ty)?;
+            constants.push(Item {
+                name: decoded(constant.name)?,
+                index: None,
+                value: Some(decode_constant(&ty, &decoded(constant.value)?)),
+                signature: Some(ty),
+                docs: decoded(constant.documentation)?,
+            })
+        }
+        let mut errors = vec![];
+        for (position, error) in decoded(module.errors)?.into_iter().enumerate() {
+            errors.push(Item {
+                name: decoded(error.name)?,
+                index: item_index(position)?,
+                signature: None,
+                value: None,
+                docs: decoded(error.documentation)?,
+            })
+        }
+        pallets.push(Pallet {
+            name: decoded(module.name)?,
+            index: module.index,
+            calls,
+            storage,
+            constants,
+            events,
+            errors,
+        })
+    }
+    pallets.sort_by_key(|pallet| pallet.index);
+    Ok(pallets)
+}
+
+/// Read saved metadata.  Both the raw SCALE encoding and the hex string
+/// returned by the `state_getMetadata` RPC call are accepted.
+fn read_metadata(path: &Path) -> Result<Vec<u8>, Error> {
+    let contents = fs::read(path)?;
+    match std::str::from_utf8(&contents).map(str::trim) {
+        Ok(hex) if hex.starts_with("0x") => Ok(hex::decode(&hex[2..])?),
+        _ => Ok(contents),
+    }
+}
+
+impl Pallet {
+    /// The items of the pallet, by kind
+    fn kinds(&self) -> [(&'static str, &[Item]); 5] {
+        [
+            ("calls", &self.calls[..]),
+            ("storage", &self.storage[..]),
+            ("constants", &self.constants[..]),
+            ("events", &self.events[..]),
+            ("errors", &self.errors[..]),
+        ]
+    }
+
+    /// Format the pallet for display.  Errors are shown with all of their
+    /// documentation, and other items with only its first line.
+    fn format(&self) -> Result<String, Error> {
+        let mut output = format!("Pallet {} (index {})\n", self.name, self.index);
+        for &(kind, items) in &self.kinds() {
+            if items.is_empty() {
+                continue
+            }
+            writeln!(
+                output,
+                "\n{}{}:",
+                kind[..1].to_ascii_uppercase(),
+                &kind[1..]
+            )?;
+            for item in items {
+                writeln!(output, "    {}", item.format())?;
+                let docs = match kind {
+                    "errors" => &item.docs[..],
+                    _ => &item.docs[..item.docs.len().min(1)],
+                };
+                for line in docs {
+                    writeln!(output, "        {}", line.trim())?
+                }
+            }
+        }
+        Ok(output)
+    }
+}
+
+/// Compare two versions of the metadata.  Changes to documentation are
+/// ignored, but changes to the indices of calls, events, and errors are not,
+/// as they change how those are encoded.
+pub(crate) fn diff(old: &[Pallet], new: &[Pallet]) -> Vec<Change> {
+    fn by_name(pallets: &[Pallet]) -> BTreeMap<&str, &Pallet> {
+        pallets
+            .iter()
+            .map(|pallet| (&*pallet.name, pallet))
+            .collect()
+    }
+    let (old, new) = (by_name(old), by_name(new));
+    let mut changes = vec![];
+    let mut change = |pallet: &str, kind, name: &str, old: Option<String>, new: Option<String>| {
+        changes.push(Change {
+            pallet: pallet.to_owned(),
+            kind,
+            name: name.to_owned(),
+            old,
+            new,
+        })
+    };
+    let index = |pallet: &Pallet| Some(format!("index {}", pallet.index));
+    for (&name, &old_pallet) in &old {
+        let new_pallet = match new.get(name) {
+            Some(&new_pallet) => new_pallet,
+            None => {
+                change(name, "pallet", name, index(old_pallet), None);
+                continue
+            }
+        };
+        if old_pallet.index != new_pallet.index {
+            change(name, "pallet", name, index(old_pallet), index(new_pallet))
+        }
+        for (&(kind, old_items), &(_, new_items)) in
+            old_pallet.kinds().iter().zip(&new_pallet.kinds())
+        {
+            for old_item in old_items {
+                match new_items.iter().find(|item| item.name == old_item.name) {
+                    None => change(name, kind, &old_item.name, Some(old_item.describe()), None),
+                    Some(new_item)
+                        if (new_item.index, &new_item.signature, &new_item.value)
+                            != (old_item.index, &old_item.signature, &old_item.value) =>
+                    {
+                        change(
+                            name,
+                            kind,
+                            &old_item.name,
+                            Some(old_item.describe()),
+                            Some(new_item.describe()),
+                        )
+                    }
+                    Some(_) => {}
+                }
+            }
+            for new_item in new_items {
+                if !old_items.iter().any(|item| item.name == new_item.name) {
+                    change(name, kind, &new_item.name, None, Some(new_item.describe()))
+                }
+            }
+        }
+    }
+    for (&name, &new_pallet) in &new {
+        if !old.contains_key(name) {
+            change(name, "pallet", name, None, index(new_pallet))
+        }
+    }
+    changes
+}
+
+/// Format a list of changes for display
+fn format_changes(changes: &[Change]) -> Result<String, Error> {
+    if changes.is_empty() {
+        return Ok("No changes\n".to_owned())
+    }
+    let mut output = String::new();
+    for change in changes {
+        let prefix = format!("{} {}", change.pallet, change.kind);
+        match (&change.old, &change.new) {
+            (None, Some(new)) => writeln!(output, "+ {}: {}", prefix, new)?,
+            (Some(old), None) => writeln!(output, "- {}: {}", prefix, old)?,
+            (Some(old), Some(new)) => writeln!(output, "~ {}: {}\n    now {}", prefix, old, new)?,
+            (None, None) => unreachable!("a change has an old or a new version"),
+        }
+    }
+    Ok(output)
+}
+
+pub(crate) async fn main(
+    cmd: Metadata,
+    rpc: &jsonrpsee::Client,
+    output: OutputFormat,
+    at: Option<H256>,
+) -> Result<(), Error> {
+    match cmd {
+        Metadata::Pallets => {
+            let pallets = pallets(&crate::rpc::raw_metadata(rpc, at).await?)?;
+            match output {
+                OutputFormat::Json => {
+                    let pallets: Vec<_> = pallets
+                        .iter()
+                        .map(|pallet| serde_json::json!({ "name": pallet.name, "index": pallet.index }))
+                        .collect();
+                    print_json(&pallets)?
+                }
+                OutputFormat::Text => {
+                    for pallet in pallets {
+                        println!("{:>3} {}", pallet.index, pallet.name)
+                    }
+                }
+            }
+        }
+        Metadata::Pallet { name } => {
+            let pallets = pallets(&crate::rpc::raw_metadata(rpc, at).await?)?;
+            let pallet = match pallets.iter().find(|pallet| pallet.name == name) {
+                Some(pallet) => pallet,
+                None => pallets
+                    .iter()
+                    .find(|pallet| pallet.name.eq_ignore_ascii_case(&name))
+                    .ok_or_else(|| format!("No pallet named {}", name))?,
+            };
+            match output {
+                OutputFormat::Json => print_json(pallet)?,
+                OutputFormat::Text => print!("{}", pallet.format()?),
+            }
+        }
+        Metadata::Save { file } => {
+            let metadata = crate::rpc::raw_metadata(rpc, at).await?;
+            // Check that the metadata can be read back before saving it.
+            pallets(&metadata)?;
+            fs::write(file, metadata)?
+        }
+        Metadata::Diff { old, new } => {
+            let changes = diff(
+                &pallets(&read_metadata(&old)?)?,
+                &pallets(&read_metadata(&new)?)?,
+            );
+            match output {
+                OutputFormat::Json => print_json(&changes)?,
+                OutputFormat::Text => print!("{}", format_changes(&changes)?),
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use codec::Encode;
+
+    #[test]
+    fn decode_constant_works() {
+        assert_eq!(decode_constant("EraIndex", &28_u32.encode()), "28");
+        assert_eq!(decode_constant("BalanceOf<T>", &10_u128.encode()), "10");
+        assert_eq!(decode_constant("Perbill", &500_000_000_u32.encode()), "50%");
+        assert_eq!(decode_constant("Percent", &[7]), "7%");
+        // Wrong length
+        assert_eq!(decode_constant("u32", &[1, 0]), "0x0100");
+        assert_eq!(
+            decode_constant("ModuleId", b"py/trsry"),
+            "0x70792f7472737279"
+        );
+    }
+
+    #[test]
+    fn diff_works() {
+        let item = |name: &str, index, signature: &str| Item {
+            name: name.to_owned(),
+            index: Some(index),
+            signature: Some(signature.to_owned()),
+            value: None,
+            docs: vec![],
+        };
+        let pallet = |name: &str, calls| Pallet {
+            name: name.to_owned(),
+            index: 6,
+            calls,
+            storage: vec![],
+            constants: vec![],
+            events: vec![],
+            errors: vec![],
+        };
+        let old = [
+            pallet("Staking", vec![
+                item("bond", 0, "(value: BalanceOf<T>)"),
+                item("chill", 1, "()"),
+            ]),
+            pallet("Sudo", vec![]),
+        ];
+        let new = [pallet("Staking", vec![
+            item("bond", 0, "(value: Compact<BalanceOf<T>>)"),
+            item("chill", 1, "()"),
+            item("kick", 2, "(who: Vec<T::AccountId>)"),
+        ])];
+        let changes = diff(&old, &new);
+        assert_eq!(
+            format_changes(&changes).unwrap(),
+            "~ Staking calls: bond(value: BalanceOf<T>) (index 0)\n    now bond(value: \
+             Compact<BalanceOf<T>>) (index 0)\n+ Staking calls: kick(who: Vec<T::AccountId>) \
+             (index 2)\n- Sudo pallet: index 6\n"
+        );
+        assert_eq!(format_changes(&diff(&new, &new)).unwrap(), "No changes\n");
+        // Reordering calls changes their encoding.
+        let reordered = [pallet("Staking", vec![
+            item("chill", 0, "()"),
+            item("bond", 1, "(value: Compact<BalanceOf<T>>)"),
+            item("kick", 2, "(who: Vec<T::AccountId>)"),
+        ])];
+        assert_eq!(
+            format_changes(&diff(&new, &reordered)).unwrap(),
+            "~ Staking calls: bond(value: Compact<BalanceOf<T>>) (index 0)\n    now bond(value: \
+             Compact<BalanceOf<T>>) (index 1)\n~ Staking calls: chill() (index 1)\n    now \
+             chill() (index 0)\n"
+        );
+    }
+}
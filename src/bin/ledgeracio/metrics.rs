@@ -0,0 +1,401 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of ledgeracio.
+//
+// ledgeracio is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// ledgeracio is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ledgeracio.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A Prometheus exporter for staking metrics
+
+use super::{Error, StructOpt};
+use crate::{common::{fetch_many, fetch_or_default, validator_statuses, At, ValidatorStatus},
+            era::era_progress,
+            payouts::{unclaimed_payouts, Payouts}};
+use async_std::{io,
+                net::{TcpListener, TcpStream},
+                prelude::*};
+use futures::{future::{join3, try_join_all},
+              stream::StreamExt as _};
+use ledgeracio::parse_targets;
+use log::warn;
+use std::{collections::{HashMap, HashSet},
+          fmt::Write as _,
+          fs,
+          io::BufReader,
+          marker::PhantomData,
+          path::PathBuf,
+          time::Duration};
+use substrate_subxt::{sp_core::crypto::{AccountId32 as AccountId, Ss58AddressFormat, Ss58Codec},
+                      staking::{BondedStore, ErasRewardPointsStore, LedgerStore, NominatorsStore},
+                      Client, KusamaRuntime};
+
+/// The largest HTTP request header that is accepted
+const MAX_REQUEST_SIZE: usize = 8192;
+
+/// How long a client may take to send its request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a client may take to receive the response
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The most connections served at once.  Each scrape reads a lot from the
+/// node, so further connections wait until one of these is done.
+const MAX_CONNECTIONS: usize = 4;
+
+#[derive(StructOpt, Debug)]
+pub(crate) struct ServeMetrics {
+    /// The address to listen on, such as `127.0.0.1:9616`
+    #[structopt(long)]
+    listen: String,
+    /// A file of stash addresses to export metrics for.  The format is that of
+    /// textual allowlists, except that each address may be followed by a
+    /// label, which is exported as the `label` label.
+    #[structopt(long)]
+    accounts: PathBuf,
+}
+
+/// A metric, along with its samples
+struct Family {
+    name: &'static str,
+    help: &'static str,
+    /// The labels and value of each sample
+    samples: Vec<(String, String)>,
+}
+
+impl Family {
+    fn new(name: &'static str, help: &'static str) -> Self {
+        Self {
+            name,
+            help,
+            samples: vec![],
+        }
+    }
+}
+
+/// Escape a label value for the Prometheus text format
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', r#"\""#)
+        .replace('\n', r"\n")
+}
+
+/// Render metric families in the Prometheus text format.  All metrics are
+/// gauges.
+fn render(families: &[Family]) -> String {
+    let mut output = String::new();
+    for family in families {
+        output += &format!(
+            "# HELP {} {}\n# TYPE {} gauge\n",
+            family.name, family.help, family.name
+        );
+        for (labels, value) in &family.samples {
+            if labels.is_empty() {
+                output += &format!("{} {}\n", family.name, value);
+            } else {
+                output += &format!("{}{{{}}} {}\n", family.name, labels, value);
+            }
+        }
+    }
+    output
+}
+
+/// Collect the metrics of the stash accounts in `accounts`
+async fn collect(
+    client: &Client<KusamaRuntime>,
+    rpc: &jsonrpsee::Client,
+    accounts: &[(AccountId, Option<String>)],
+    network: Ss58AddressFormat,
+) -> Result<String, Error> {
+    let stashes: Vec<AccountId> = accounts.iter().map(|(stash, _)| stash.clone()).collect();
+    let best = At::best(rpc);
+    let progress = era_progress(client, best).await?;
+    let era = progress.active_era;
+    let bonded: Vec<_> = stashes
+        .iter()
+        .map(|stash| BondedStore {
+            stash: stash.clone(),
+        })
+        .collect();
+    let nominations: Vec<_> = stashes
+        .iter()
+        .map(|stash| NominatorsStore {
+            stash: stash.clone(),
+        })
+        .collect();
+    let points = ErasRewardPointsStore {
+        index: era,
+        _phantom: PhantomData,
+    };
+    let (controllers, nominations, statuses) = join3(
+        fetch_many(client, &bonded, best),
+        fetch_many(client, &nominations, best),
+        validator_statuses(client, &stashes, era, best),
+    )
+    .await;
+    let (controllers, nominations, statuses) = (controllers?, nominations?, statuses?);
+    let ledgers: Vec<_> = controllers
+        .iter()
+        .flatten()
+        .map(|controller| LedgerStore {
+            controller: controller.clone(),
+        })
+        .collect();
+    let targets: HashSet<AccountId> = nominations
+        .iter()
+        .flatten()
+        .flat_map(|nominations| nominations.targets.iter().cloned())
+        .collect();
+    let targets: Vec<_> = targets.into_iter().collect();
+    let (ledgers, points, target_statuses) = join3(
+        fetch_many(client, &ledgers, best),
+        fetch_or_default(client, &points, best),
+        validator_statuses(client, &targets, era, best),
+    )
+    .await;
+    let ledgers: HashMap<_, _> = ledgers?
+        .into_iter()
+        .flatten()
+        .map(|ledger| (ledger.stash.clone(), ledger))
+        .collect();
+    let points = points?;
+    let active_targets: HashSet<&AccountId> = targets
+        .iter()
+        .zip(target_statuses?)
+        .filter(|(_, (status, _))| matches!(status, ValidatorStatus::Active(_)))
+        .map(|(target, _)| target)
+        .collect();
+    // Validators are the stashes with preferences or an exposure.
+    let unclaimed =
+        controllers
+            .iter()
+            .zip(&statuses)
+            .map(|(controller, (status, prefs))| async move {
+                match controller {
+                    Some(controller)
+                        if prefs.is_some() || matches!(status, ValidatorStatus::Active(_)) =>
+                    {
+                        Ok(Some(
+                            unclaimed_payouts(controller.clone(), client, best).await?,
+                        ))
+                    }
+                    _ => Ok::<_, Error>(None),
+                }
+            });
+    let unclaimed = try_join_all(unclaimed).await?;
+
+    let mut bonded = Family::new(
+        "ledgeracio_bonded",
+        "Total balance bonded by the stash, in planck",
+    );
+    let mut active = Family::new(
+        "ledgeracio_active",
+        "Balance of the stash at stake, in planck",
+    );
+    let mut unlocking = Family::new(
+        "ledgeracio_unlocking",
+        "Balance of the stash being unbonded, in planck",
+    );
+    let mut commission = Family::new(
+        "ledgeracio_commission_ratio",
+        "Current commission of the validator, from 0 to 1",
+    );
+    let mut active_set = Family::new(
+        "ledgeracio_active_set",
+        "1 if the validator is in the active set, otherwise 0",
+    );
+    let mut era_points = Family::new(
+        "ledgeracio_era_points",
+        "Reward points of the validator in the active era",
+    );
+    let mut unclaimed_eras = Family::new(
+        "ledgeracio_unclaimed_eras",
+        "Number of ended eras with unclaimed validator payouts",
+    );
+    let mut inactive_targets = Family::new(
+        "ledgeracio_inactive_targets",
+        "Number of nomination targets not in the active set",
+    );
+    let mut active_era = Family::new("ledgeracio_active_era", "Index of the active era");
+    active_era.samples.push((String::new(), era.to_string()));
+    for ((((stash, label), nominations), (status, prefs)), payouts) in accounts
+        .iter()
+        .zip(nominations)
+        .zip(statuses)
+        .zip(unclaimed)
+    {
+        let mut labels = format!(
+            "stash=\"{}\"",
+            escape_label(&stash.to_ss58check_with_version(network))
+        );
+        if let Some(label) = label {
+            write!(labels, ",label=\"{}\"", escape_label(label))?;
+        }
+        if let Some(ledger) = ledgers.get(stash) {
+            let unlocking_total = ledger
+                .unlocking
+                .iter()
+                .fold(0_u128, |total, chunk| total.saturating_add(chunk.value));
+            bonded
+                .samples
+                .push((labels.clone(), ledger.total.to_string()));
+            active
+                .samples
+                .push((labels.clone(), ledger.active.to_string()));
+            unlocking
+                .samples
+                .push((labels.clone(), unlocking_total.to_string()));
+        }
+        if let Some(prefs) = prefs {
+            commission.samples.push((
+                labels.clone(),
+                format!("{}", f64::from(prefs.commission.deconstruct()) / 1e9),
+            ));
+        }
+        if prefs.is_some() || matches!(status, ValidatorStatus::Active(_)) {
+            let is_active = matches!(status, ValidatorStatus::Active(_));
+            active_set
+                .samples
+                .push((labels.clone(), u8::from(is_active).to_string()));
+            let points = points.individual.get(stash).cloned().unwrap_or_default();
+            era_points
+                .samples
+                .push((labels.clone(), points.to_string()));
+        }
+        if let Some(Payouts { eras, .. }) = payouts {
+            let count = eras.iter().filter(|era| era.reward.is_some()).count();
+            unclaimed_eras
+                .samples
+                .push((labels.clone(), count.to_string()));
+        }
+        if let Some(nominations) = nominations {
+            let count = nominations
+                .targets
+                .iter()
+                .filter(|target| !active_targets.contains(target))
+                .count();
+            inactive_targets.samples.push((labels, count.to_string()));
+        }
+    }
+    Ok(render(&[
+        active_era,
+        bonded,
+        active,
+        unlocking,
+        commission,
+        active_set,
+        era_points,
+        unclaimed_eras,
+        inactive_targets,
+    ]))
+}
+
+/// Read an HTTP request header from `stream`, or return `None` if the client
+/// closes the connection first or the header is too large
+async fn read_request(stream: &mut TcpStream) -> io::Result<Option<Vec<u8>>> {
+    let mut request = vec![];
+    let mut buf = [0_u8; 1024];
+    while !request.ends_with(b"\r\n\r\n") {
+        let read = stream.read(&mut buf).await?;
+        if read == 0 || request.len() + read > MAX_REQUEST_SIZE {
+            return Ok(None)
+        }
+        request.extend_from_slice(&buf[..read]);
+    }
+    Ok(Some(request))
+}
+
+/// Answer a single HTTP request.  Only `GET /metrics` is supported.
+async fn serve(
+    mut stream: TcpStream,
+    client: &Client<KusamaRuntime>,
+    rpc: &jsonrpsee::Client,
+    accounts: &[(AccountId, Option<String>)],
+    network: Ss58AddressFormat,
+) -> Result<(), Error> {
+    let request = match io::timeout(REQUEST_TIMEOUT, read_request(&mut stream)).await? {
+        Some(request) => request,
+        None => return Ok(()),
+    };
+    let request = String::from_utf8_lossy(&request);
+    let mut words = request.split_whitespace();
+    let (status, body) = match (words.next(), words.next()) {
+        (Some("GET"), Some("/metrics")) => match collect(client, rpc, accounts, network).await {
+            Ok(metrics) => ("200 OK", metrics),
+            Err(e) => {
+                warn!("Failed to collect metrics: {}", e);
+                ("500 Internal Server Error", format!("{}\n", e))
+            }
+        },
+        _ => ("404 Not Found", "Not found: try /metrics\n".to_owned()),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: \
+         {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    io::timeout(RESPONSE_TIMEOUT, stream.write_all(response.as_bytes())).await?;
+    Ok(())
+}
+
+/// Serve metrics until killed.  Up to [`MAX_CONNECTIONS`] connections are
+/// served concurrently, using the same connection to the node.
+pub(crate) async fn main(
+    cmd: ServeMetrics,
+    client: &Client<KusamaRuntime>,
+    rpc: &jsonrpsee::Client,
+    network: Ss58AddressFormat,
+) -> Result<(), Error> {
+    let ServeMetrics { listen, accounts } = cmd;
+    let accounts = parse_targets(BufReader::new(fs::File::open(&accounts)?), network)?;
+    if accounts.is_empty() {
+        return Err("No accounts to export metrics for".to_owned().into())
+    }
+    let listener = TcpListener::bind(&*listen).await?;
+    eprintln!("Serving metrics on http://{}/metrics", listen);
+    let accounts = &*accounts;
+    listener
+        .incoming()
+        .for_each_concurrent(MAX_CONNECTIONS, |stream| async move {
+            let result = match stream {
+                Ok(stream) => serve(stream, client, rpc, accounts, network).await,
+                Err(e) => Err(e.into()),
+            };
+            if let Err(e) = result {
+                warn!("Error serving metrics: {}", e)
+            }
+        })
+        .await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_works() {
+        let mut family = Family::new("ledgeracio_test", "A test");
+        family.samples.push((
+            format!("label=\"{}\"", escape_label("a\"b\\c\nd")),
+            "1".to_owned(),
+        ));
+        family.samples.push((String::new(), "2".to_owned()));
+        assert_eq!(
+            render(&[family]),
+            "# HELP ledgeracio_test A test\n# TYPE ledgeracio_test \
+             gauge\nledgeracio_test{label=\"a\\\"b\\\\c\\nd\"} 1\nledgeracio_test 2\n"
+        );
+    }
+}
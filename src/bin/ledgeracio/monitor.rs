@@ -0,0 +1,599 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of ledgeracio.
+//
+// ledgeracio is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// ledgeracio is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ledgeracio.  If not, see <http://www.gnu.org/licenses/>.
+
+//! The `monitor` command, which runs hooks when events concerning a set of
+//! stashes happen
+
+use super::{Error, StructOpt};
+use crate::{common::{fetch, fetch_many, fetch_or_default, fetch_raw, At},
+            offences::{ConcurrentReportsIndexStore, Kind, OffenceDetails, ReportsStore},
+            payouts::{unclaimed_nominator_payouts, unclaimed_payouts, Payouts,
+                      EXPIRY_WARNING_ERAS},
+            rpc::BlockId,
+            staking::EraIndex,
+            watch::{block_events, intention_keys, StakingEvent}};
+use async_std::task::{self, spawn_blocking};
+use codec::Decode;
+use ledgeracio::{parse_address, validate_network, Balance};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet,
+          fs,
+          io::{BufReader, Read, Write},
+          marker::PhantomData,
+          path::PathBuf,
+          process,
+          sync::Arc,
+          time::Duration};
+use substrate_subxt::{sp_core::{crypto::{AccountId32 as AccountId, Ss58AddressFormat, Ss58Codec},
+                                storage::{StorageData, StorageKey},
+                                H256},
+                      staking::{ActiveEraStore, BondedStore},
+                      Client, KusamaRuntime};
+
+#[derive(StructOpt, Debug)]
+pub(crate) struct Monitor {
+    /// The configuration file, which lists the stashes to monitor and the
+    /// rules to apply.  See the README for its format.
+    config: PathBuf,
+}
+
+/// Something that can happen to a stash, or to the chain
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+enum Trigger {
+    /// A stash was paid a reward
+    Reward,
+    /// A stash was slashed
+    Slash,
+    /// A nominator was removed from the nominators of a validator
+    Kick,
+    /// A stash stopped validating or nominating
+    Chill,
+    /// A new session started
+    Session,
+    /// An offence by a validator, or by a validator backed by a nominator,
+    /// was reported
+    Offence,
+    /// An unclaimed payout will soon expire
+    PayoutExpiring,
+}
+
+/// What to do when an event happens
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+struct Rule {
+    /// The events the rule applies to
+    on: Vec<Trigger>,
+    /// A command to run, followed by its arguments.  The notification is
+    /// written to its standard input.
+    #[serde(default)]
+    command: Vec<String>,
+    /// A FIFO to write the notification to
+    fifo: Option<PathBuf>,
+}
+
+/// The contents of the configuration file
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    /// The stash accounts to monitor
+    stashes: Vec<String>,
+    /// How many eras before an unclaimed payout expires to notify about it
+    #[serde(default = "default_expiry_eras")]
+    expiry_eras: EraIndex,
+    rules: Vec<Rule>,
+}
+
+fn default_expiry_eras() -> EraIndex { EXPIRY_WARNING_ERAS }
+
+/// A notification of an event, which is written as a line of JSON
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+struct Notification {
+    event: Trigger,
+    /// The number of the finalized block in which the event was seen
+    block: u32,
+    /// The stash concerned, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stash: Option<String>,
+    /// A description of the event
+    message: String,
+}
+
+/// Parse and check a configuration file, returning the stashes to monitor and
+/// the configuration
+fn parse_config<T: Read>(
+    reader: T,
+    network: Ss58AddressFormat,
+) -> Result<(Vec<AccountId>, Config), Error> {
+    let config: Config = serde_json::from_reader(reader)?;
+    let mut stashes = vec![];
+    for address in &config.stashes {
+        let (stash, provided_network) = parse_address(address)?;
+        validate_network(address, provided_network, network)?;
+        stashes.push(stash)
+    }
+    if stashes.is_empty() {
+        return Err("No stashes to monitor".to_owned().into())
+    }
+    for (number, rule) in (1..).zip(&config.rules) {
+        if rule.command.is_empty() && rule.fifo.is_none() {
+            return Err(format!("Rule {} has neither a command nor a fifo", number).into())
+        }
+    }
+    Ok((stashes, config))
+}
+
+/// Run `command` with `line` as its standard input, and wait for it to exit
+fn run_command(command: &[String], line: &str) -> Result<(), Error> {
+    let mut child = process::Command::new(&command[0])
+        .args(&command[1..])
+        .stdin(process::Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(line.as_bytes())?;
+    }
+    let status = child.wait()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("{} failed: {}", command[0], status).into())
+    }
+}
+
+/// Deliver a notification to every rule that applies to it.  Delivery happens
+/// in the background, so a slow command or a FIFO with no reader does not stop
+/// the monitor.
+fn notify(rules: &Arc<Vec<Rule>>, notification: &Notification) -> Result<(), Error> {
+    let mut line = serde_json::to_string(notification)?;
+    line.push('\n');
+    info!("{}", line.trim_end());
+    for (index, rule) in rules.iter().enumerate() {
+        if !rule.on.contains(&notification.event) {
+            continue
+        }
+        let (rules, line) = (rules.clone(), line.clone());
+        spawn_blocking(move || {
+            let rule = &rules[index];
+            if !rule.command.is_empty() {
+                if let Err(e) = run_command(&rule.command, &line) {
+                    warn!("Could not run {}: {}", rule.command[0], e)
+                }
+            }
+            if let Some(fifo) = &rule.fifo {
+                // Opening a FIFO blocks until there is a reader.
+                let result = fs::OpenOptions::new()
+                    .write(true)
+                    .open(fifo)
+                    .and_then(|mut fifo| fifo.write_all(line.as_bytes()));
+                if let Err(e) = result {
+                    warn!("Could not write to {}: {}", fifo.display(), e)
+                }
+            }
+        });
+    }
+    Ok(())
+}
+
+/// Find the reported offences announced by an `Offence` event with data
+/// `data` that concern one of `stashes`, either as the offender or as one of
+/// its nominators.  The reports are read at block `at`.
+async fn offences(
+    client: &Client<KusamaRuntime>,
+    mut data: &[u8],
+    stashes: &[AccountId],
+    network: Ss58AddressFormat,
+    at: At<'_>,
+) -> Result<Vec<(AccountId, String)>, Error> {
+    let (kind, time_slot) = <(Kind, Vec<u8>)>::decode(&mut data)?;
+    let index = ConcurrentReportsIndexStore {
+        kind,
+        time_slot,
+        _runtime: PhantomData,
+    };
+    let reports: Vec<_> = fetch_or_default(client, &index, at)
+        .await?
+        .into_iter()
+        .map(|report_id: H256| ReportsStore { report_id })
+        .collect();
+    let kind = String::from_utf8_lossy(&kind);
+    let kind = kind.trim_end_matches('\0');
+    let mut found = vec![];
+    for OffenceDetails {
+        offender: (offender, exposure),
+        ..
+    } in fetch_many(client, &reports, at)
+        .await?
+        .into_iter()
+        .flatten()
+    {
+        let offender_address = offender.to_ss58check_with_version(network);
+        if stashes.contains(&offender) {
+            found.push((
+                offender.clone(),
+                format!("Offence: {} was reported for {}", offender_address, kind),
+            ))
+        }
+        for nominator in exposure.others {
+            if stashes.contains(&nominator.who) {
+                found.push((
+                    nominator.who.clone(),
+                    format!(
+                        "Offence: {}, backed by {} with {}, was reported for {}",
+                        offender_address,
+                        nominator.who.to_ss58check_with_version(network),
+                        Balance::new(nominator.value, client.properties()),
+                        kind
+                    ),
+                ))
+            }
+        }
+    }
+    Ok(found)
+}
+
+/// Find the unclaimed payouts of `stashes` that expire within `expiry_eras`
+/// eras at block `at`.  Each payout is identified by the stash, the era, and
+/// the validator.
+async fn expiring_payouts(
+    client: &Client<KusamaRuntime>,
+    stashes: &[AccountId],
+    expiry_eras: EraIndex,
+    network: Ss58AddressFormat,
+    at: At<'_>,
+) -> Result<Vec<((AccountId, EraIndex, AccountId), String)>, Error> {
+    let bonded: Vec<_> = stashes
+        .iter()
+        .map(|stash| BondedStore {
+            stash: stash.clone(),
+        })
+        .collect();
+    let mut found = vec![];
+    for (stash, controller) in stashes.iter().zip(fetch_many(client, &bonded, at).await?) {
+        let controller = match controller {
+            Some(controller) => controller,
+            None => continue,
+        };
+        let address = stash.to_ss58check_with_version(network);
+        let Payouts {
+            eras,
+            history_start,
+            ..
+        } = unclaimed_payouts(controller.clone(), client, at).await?;
+        for era in eras.iter().filter(|era| era.reward.is_some()) {
+            let remaining = era.era.saturating_sub(history_start);
+            if remaining < expiry_eras {
+                found.push((
+                    (stash.clone(), era.era, stash.clone()),
+                    format!(
+                        "Payout expiring: the payout of {} for era {} expires in {} era(s)",
+                        address,
+                        era.era,
+                        remaining + 1
+                    ),
+                ))
+            }
+        }
+        let (_, payouts) = unclaimed_nominator_payouts(controller, client, at).await?;
+        for payout in payouts {
+            let remaining = payout.era.saturating_sub(history_start);
+            if payout.share.is_some() && !payout.oversubscribed && remaining < expiry_eras {
+                found.push((
+                    (stash.clone(), payout.era, payout.validator.clone()),
+                    format!(
+                        "Payout expiring: the payout of {} from validator {} for era {} expires \
+                         in {} era(s)",
+                        address,
+                        payout.validator.to_ss58check_with_version(network),
+                        payout.era,
+                        remaining + 1
+                    ),
+                ))
+            }
+        }
+    }
+    Ok(found)
+}
+
+/// How many times to try to process a block before skipping it
+const ATTEMPTS: u32 = 3;
+
+/// How long to wait before trying again to process a block
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// The stashes to monitor, and how to report on them
+struct Monitored {
+    stashes: Vec<AccountId>,
+    /// The storage keys of the intentions of each stash to validate and to
+    /// nominate, two per stash
+    keys: Vec<StorageKey>,
+    expiry_eras: EraIndex,
+    network: Ss58AddressFormat,
+}
+
+/// What is remembered from one block to the next
+#[derive(Default)]
+struct State {
+    /// The values of [`Monitored::keys`]
+    intentions: Option<Vec<Option<StorageData>>>,
+    active_era: Option<EraIndex>,
+    /// The expiring payouts that have already been notified
+    expiring: HashSet<(AccountId, EraIndex, AccountId)>,
+}
+
+/// The notifications for the events of block `at`, which has number `number`,
+/// along with the stashes that an event reports as chilled.  Events that
+/// cannot be decoded are logged and skipped.
+async fn event_notifications(
+    client: &Client<KusamaRuntime>,
+    rpc: &jsonrpsee::Client,
+    at: H256,
+    number: u32,
+    monitored: &Monitored,
+) -> Result<(Vec<Notification>, HashSet<AccountId>), Error> {
+    let Monitored {
+        stashes, network, ..
+    } = monitored;
+    let notification = |event, stash: Option<&AccountId>, message| Notification {
+        event,
+        block: number,
+        stash: stash.map(|stash| stash.to_ss58check_with_version(*network)),
+        message,
+    };
+    let (mut notifications, mut chilled) = (vec![], HashSet::new());
+    for event in block_events(client, rpc, at, number).await? {
+        let warn_undecodable = |e: &dyn std::fmt::Display| {
+            warn!(
+                "Could not decode a {}::{} event in block {}: {}",
+                event.module, event.variant, number, e
+            )
+        };
+        match (&*event.module, &*event.variant) {
+            ("Session", "NewSession") => match u32::decode(&mut &*event.data) {
+                Ok(session) => notifications.push(notification(
+                    Trigger::Session,
+                    None,
+                    format!("Session: session {} started", session),
+                )),
+                Err(e) => warn_undecodable(&e),
+            },
+            ("Offences", "Offence") => {
+                // The reports are removed once they expire, so they are read
+                // at the block of the event.
+                for (stash, message) in
+                    offences(client, &event.data, stashes, *network, At::block(rpc, at)).await?
+                {
+                    notifications.push(notification(Trigger::Offence, Some(&stash), message))
+                }
+            }
+            (module, variant) => {
+                let event = match StakingEvent::decode(module, variant, &event.data) {
+                    Ok(Some(event)) => event,
+                    Ok(None) => continue,
+                    Err(e) => {
+                        warn_undecodable(&e);
+                        continue
+                    }
+                };
+                let stash = match event.stash(stashes) {
+                    Some(stash) => stash,
+                    None => continue,
+                };
+                let trigger = match event {
+                    StakingEvent::Reward(..) => Trigger::Reward,
+                    StakingEvent::Slash(..) => Trigger::Slash,
+                    StakingEvent::Kicked { .. } => Trigger::Kick,
+                    StakingEvent::Chilled(_) => {
+                        chilled.insert(stash.clone());
+                        Trigger::Chill
+                    }
+                };
+                notifications.push(notification(
+                    trigger,
+                    Some(stash),
+                    event.format(client.properties(), *network),
+                ))
+            }
+        }
+    }
+    Ok((notifications, chilled))
+}
+
+/// Find the notifications for block `number`.  `state` is only updated if
+/// the whole block could be read, so that it can be tried again.
+async fn process_block(
+    client: &Client<KusamaRuntime>,
+    rpc: &jsonrpsee::Client,
+    number: u32,
+    monitored: &Monitored,
+    state: &mut State,
+) -> Result<Vec<Notification>, Error> {
+    let network = monitored.network;
+    let at = crate::rpc::block_hash(rpc, BlockId::Number(number)).await?;
+    let (mut notifications, chilled) =
+        event_notifications(client, rpc, at, number, monitored).await?;
+    let notification = |event, stash: &AccountId, message| Notification {
+        event,
+        block: number,
+        stash: Some(stash.to_ss58check_with_version(network)),
+        message,
+    };
+    // Older runtimes have no `Chilled` event, so also look for intentions
+    // to validate or nominate that have disappeared.
+    let values = fetch_raw(rpc, &monitored.keys, at).await?;
+    if let Some(previous) = &state.intentions {
+        for (stash, (previous, values)) in monitored
+            .stashes
+            .iter()
+            .zip(previous.chunks(2).zip(values.chunks(2)))
+        {
+            let stopped = |i: usize| previous[i].is_some() && values[i].is_none();
+            let kind = if stopped(0) {
+                "validating"
+            } else if stopped(1) {
+                "nominating"
+            } else {
+                continue
+            };
+            if !chilled.contains(stash) {
+                notifications.push(notification(
+                    Trigger::Chill,
+                    stash,
+                    format!(
+                        "Chilled: {} is no longer {}",
+                        stash.to_ss58check_with_version(network),
+                        kind
+                    ),
+                ))
+            }
+        }
+    }
+    // Payouts only come closer to expiring when the active era changes.
+    let era = fetch(
+        client,
+        &ActiveEraStore {
+            _runtime: PhantomData,
+        },
+        At::block(rpc, at),
+    )
+    .await?
+    .map(|era| era.index);
+    let mut expiring = vec![];
+    if era != state.active_era {
+        for (payout, message) in expiring_payouts(
+            client,
+            &monitored.stashes,
+            monitored.expiry_eras,
+            network,
+            At::block(rpc, at),
+        )
+        .await?
+        {
+            if !state.expiring.contains(&payout) {
+                notifications.push(notification(Trigger::PayoutExpiring, &payout.0, message));
+                expiring.push(payout)
+            }
+        }
+    }
+    state.intentions = Some(values);
+    state.active_era = era;
+    state.expiring.extend(expiring);
+    Ok(notifications)
+}
+
+/// Follow finalized blocks, and deliver a notification whenever an event
+/// happens.  A block that cannot be read is tried again a few times, and then
+/// skipped.  This runs until the connection to the node is lost.
+pub(crate) async fn main(
+    cmd: Monitor,
+    client: &Client<KusamaRuntime>,
+    rpc: &jsonrpsee::Client,
+    network: Ss58AddressFormat,
+) -> Result<(), Error> {
+    let (stashes, config) = parse_config(BufReader::new(fs::File::open(&cmd.config)?), network)?;
+    let Config {
+        expiry_eras, rules, ..
+    } = config;
+    let rules = Arc::new(rules);
+    let mut keys = vec![];
+    for stash in &stashes {
+        let (validator, nominator) = intention_keys(client, stash)?;
+        keys.push(validator);
+        keys.push(nominator);
+    }
+    let monitored = Monitored {
+        stashes,
+        keys,
+        expiry_eras,
+        network,
+    };
+    let mut heads = client.subscribe_finalized_blocks().await?;
+    let (mut state, mut last) = (State::default(), None);
+    loop {
+        let head = heads.next().await.number;
+        // Finality can advance by several blocks at once, and the
+        // subscription only reports the last of them.
+        let first = last.map_or(head, |last: u32| last + 1);
+        last = Some(head);
+        for number in first..=head {
+            let mut attempt = 1;
+            let notifications = loop {
+                match process_block(client, rpc, number, &monitored, &mut state).await {
+                    Ok(notifications) => break notifications,
+                    Err(e) if attempt < ATTEMPTS => {
+                        warn!("Could not read block #{}, retrying: {}", number, e);
+                        attempt += 1;
+                        task::sleep(RETRY_DELAY).await
+                    }
+                    Err(e) => {
+                        warn!("Could not read block #{}, skipping it: {}", number, e);
+                        break vec![]
+                    }
+                }
+            };
+            for notification in &notifications {
+                notify(&rules, notification)?
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_config_works() {
+        let network = Ss58AddressFormat::KusamaAccount;
+        let stash = AccountId::from([1; 32]);
+        let address = stash.to_ss58check_with_version(network);
+        let config = format!(
+            r#"{{
+                "stashes": ["{}"],
+                "rules": [
+                    {{ "on": ["chill", "slash"], "command": ["page", "--urgent"] }},
+                    {{ "on": ["payout-expiring"], "fifo": "/run/ledgeracio" }}
+                ]
+            }}"#,
+            address
+        );
+        let (stashes, config) = parse_config(config.as_bytes(), network).unwrap();
+        assert_eq!(stashes, vec![stash]);
+        assert_eq!(config.expiry_eras, EXPIRY_WARNING_ERAS);
+        assert_eq!(config.rules, vec![
+            Rule {
+                on: vec![Trigger::Chill, Trigger::Slash],
+                command: vec!["page".to_owned(), "--urgent".to_owned()],
+                fifo: None,
+            },
+            Rule {
+                on: vec![Trigger::PayoutExpiring],
+                command: vec![],
+                fifo: Some("/run/ledgeracio".into()),
+            },
+        ]);
+        let no_action = format!(
+            r#"{{ "stashes": ["{}"], "rules": [{{ "on": ["offence"] }}] }}"#,
+            address
+        );
+        assert!(parse_config(no_action.as_bytes(), network).is_err());
+        let wrong_network = format!(
+            r#"{{ "stashes": ["{}"], "rules": [] }}"#,
+            stash.to_ss58check_with_version(Ss58AddressFormat::PolkadotAccount)
+        );
+        assert!(parse_config(wrong_network.as_bytes(), network).is_err());
+    }
+}
@@ -16,15 +16,34 @@
 
 //! Nominator commands
 
-use super::{parse_reward_destination, AccountType, Error, LedgeracioPath, StructOpt};
-use crate::common::pad;
+use super::{parse_reward_destination, validate_reward_destination, AccountType, Error,
+            LedgeracioPath, StructOpt};
+use crate::{common::{fetch, fetch_many, format_reward_destination, format_unlocking, payee_code,
+                     unlocking_json, validator_statuses, validators_json, At, UnlockChunkJson,
+                     ValidatorJson, ValidatorStatus},
+            era::{display_era, era_progress, EraJson, EraProgress},
+            output::{print_json, Amount, OutputFormat},
+            staking::{EraIndex, SlashingSpansStore, ValidatorPrefs}};
 use core::{future::Future, pin::Pin};
-use ledgeracio::{parse_address, validate_network};
+use futures::future::{join, join3, try_join_all};
+use ledgeracio::{inspect_allowlist, parse_address, parse_public, parse_targets, validate_network,
+                 Balance};
+use serde::Serialize;
+use std::{collections::HashSet,
+          fmt::Write as _,
+          fs,
+          io::BufReader,
+          path::{Path, PathBuf}};
 use substrate_subxt::{sp_core::{crypto::{AccountId32 as AccountId, Ss58AddressFormat, Ss58Codec},
                                 H256},
                       staking::{BondedStore, ChillCallExt, LedgerStore, NominateCallExt,
-                                PayeeStore, RewardDestination, SetPayeeCallExt},
-                      Client, KusamaRuntime};
+                                Nominations, NominatorsStore, PayeeStore, RewardDestination,
+                                SetPayeeCallExt, StakingLedger},
+                      Client, KusamaRuntime, Signer};
+
+/// The maximum number of nominations, used if the chain metadata does not
+/// specify it
+const MAX_NOMINATIONS: usize = 16;
 
 #[derive(StructOpt, Debug)]
 pub(crate) enum Nominator {
@@ -34,13 +53,34 @@ pub(crate) enum Nominator {
         address: (AccountId, u8),
     },
     /// Show the specified stash controller, or all if none is specified.
-    Show { index: Option<u32> },
+    Show {
+        index: Option<u32>,
+        /// Keep running, showing the status again whenever it changes on
+        /// chain, along with staking events concerning the nominators
+        #[structopt(long)]
+        watch: bool,
+    },
+    /// Show the unclaimed payouts of the specified nominator, or of all
+    /// nominators if none is specified.
+    Payouts { index: Option<u32> },
     /// Nominate a new validator set
     #[structopt(name = "nominate")]
     Nominate {
         index: u32,
         #[structopt(parse(try_from_str = parse_address))]
         set: Vec<(AccountId, u8)>,
+        /// A file of validator stash addresses to nominate, in addition to any
+        /// given on the command line.  The format is that of textual
+        /// allowlists, except that each address may be followed by a label.
+        #[structopt(long)]
+        targets_file: Option<PathBuf>,
+        /// A signed allowlist to check the nominations against before sending
+        /// them to the device
+        #[structopt(long, requires = "allowlist-key")]
+        allowlist: Option<PathBuf>,
+        /// The public key file that signed the allowlist
+        #[structopt(long, requires = "allowlist")]
+        allowlist_key: Option<PathBuf>,
     },
     /// Chill (announce intention to cease nomination)
     Chill { index: u32 },
@@ -48,97 +88,528 @@ pub(crate) enum Nominator {
     #[structopt(name = "set-payee")]
     SetPayee {
         index: u32,
+        /// `staked`, `stash`, `controller`, `none`, or `account:<address>`
         #[structopt(parse(try_from_str = parse_reward_destination))]
-        target: RewardDestination<AccountId>,
+        target: RewardDestination<(AccountId, u8)>,
     },
     /// Display the address of the given index
     Address { index: u32 },
 }
 
-async fn display_nominators(
-    controller: AccountId,
+impl Nominator {
+    /// Whether the command supports `--output json`
+    pub(crate) fn supports_json(&self) -> bool {
+        match self {
+            Self::ShowAddress { .. } | Self::Payouts { .. } | Self::Address { .. } => true,
+            Self::Show { watch, .. } => !watch,
+            Self::Nominate { .. } | Self::Chill { .. } | Self::SetPayee { .. } => false,
+        }
+    }
+
+    /// Whether the command signs and submits a transaction
+    pub(crate) fn submits(&self) -> bool {
+        match self {
+            Self::Nominate { .. } | Self::Chill { .. } | Self::SetPayee { .. } => true,
+            Self::Show { .. }
+            | Self::ShowAddress { .. }
+            | Self::Payouts { .. }
+            | Self::Address { .. } => false,
+        }
+    }
+
+    /// Whether the command supports `--verify-proofs`
+    pub(crate) fn supports_verify(&self) -> bool {
+        match self {
+            Self::Show { watch, .. } => !watch,
+            Self::ShowAddress { .. }
+            | Self::Payouts { .. }
+            | Self::Nominate { .. }
+            | Self::Chill { .. }
+            | Self::SetPayee { .. }
+            | Self::Address { .. } => true,
+        }
+    }
+
+    /// Whether the command supports `--at`
+    pub(crate) fn supports_at(&self) -> bool {
+        match self {
+            Self::ShowAddress { .. } | Self::Payouts { .. } => true,
+            Self::Show { watch, .. } => !watch,
+            Self::Nominate { .. }
+            | Self::Chill { .. }
+            | Self::SetPayee { .. }
+            | Self::Address { .. } => false,
+        }
+    }
+}
+
+/// Display the status of the nominators with controllers `controllers` at
+/// block `at`
+async fn display_show(
+    client: &Client<KusamaRuntime>,
+    controllers: &[AccountId],
+    network: Ss58AddressFormat,
+    at: At<'_>,
+) -> Result<String, Error> {
+    let nominators = controllers
+        .iter()
+        .map(|controller| display_nominators(controller.clone(), client, network, at));
+    let (era, nominators) = join(display_era(client, at), try_join_all(nominators)).await;
+    Ok(format!("{}\n{}", era?, nominators?.concat()))
+}
+
+/// Print the status of the nominators with controllers `controllers` at block
+/// `at` as JSON
+async fn print_show_json(
     client: &Client<KusamaRuntime>,
+    controllers: &[AccountId],
     network: Ss58AddressFormat,
+    at: At<'_>,
 ) -> Result<(), Error> {
-    use substrate_subxt::staking::{NominatorsStore, StakingLedger};
+    let nominators = controllers
+        .iter()
+        .map(|controller| nominator_json(controller.clone(), client, network, at));
+    let (progress, nominators) = join(era_progress(client, at), try_join_all(nominators)).await;
+    print_json(&serde_json::json!({
+        "era": EraJson::from(&progress?),
+        "nominators": nominators?,
+    }))
+}
+
+/// What is shown about a nominator
+struct NominatorInfo {
+    ledger: StakingLedger<AccountId, u128>,
+    payee: RewardDestination<AccountId>,
+    progress: EraProgress,
+    /// The nominations, if any, along with what is shown about each target
+    nominations: Option<(Nominations<AccountId>, Vec<TargetInfo>)>,
+}
+
+/// What is shown about a nomination target
+struct TargetInfo {
+    status: ValidatorStatus,
+    prefs: Option<ValidatorPrefs>,
+    /// The controller of the target, if it has one
+    controller: Option<AccountId>,
+    /// The era in which the target was last slashed, if that was after the
+    /// nominations were submitted.  The runtime then ignores the nomination
+    /// until it is submitted again.
+    slashed_in: Option<EraIndex>,
+}
+
+/// Fetch what is shown about the nominator controlled by `controller` at block
+/// `at`
+async fn fetch_nominator_info(
+    controller: &AccountId,
+    client: &Client<KusamaRuntime>,
+    at: At<'_>,
+) -> Result<NominatorInfo, Error> {
     let store = LedgerStore {
         controller: controller.clone(),
     };
-    let StakingLedger {
-        stash,
-        total,
-        active,
-        unlocking,
-        claimed_rewards: _, // not updated for nominators
-    } = client
-        .fetch(&store, None)
+    let ledger = fetch(client, &store, at)
         .await?
         .ok_or_else(|| format!("No nominator account found for controller {}", controller))?;
-    let payee = client
-        .fetch(
-            &PayeeStore {
-                stash: stash.clone(),
-            },
-            None,
+    let payee = PayeeStore {
+        stash: ledger.stash.clone(),
+    };
+    let nominations = NominatorsStore {
+        stash: ledger.stash.clone(),
+    };
+    let (payee, nominations, progress) = join3(
+        fetch(client, &payee, at),
+        fetch(client, &nominations, at),
+        era_progress(client, at),
+    )
+    .await;
+    let payee = payee?.ok_or_else(|| {
+        format!(
+            "No payee found for controller {} (this is a bug)",
+            controller
         )
-        .await?
-        .ok_or_else(|| {
-            format!(
-                "No payee found for controller {} (this is a bug)",
-                controller
+    })?;
+    let progress = progress?;
+    let nominations = match nominations? {
+        None => None,
+        Some(nominations) => {
+            let bonded: Vec<_> = nominations
+                .targets
+                .iter()
+                .map(|target| BondedStore {
+                    stash: target.clone(),
+                })
+                .collect();
+            let spans: Vec<_> = nominations
+                .targets
+                .iter()
+                .map(|target| SlashingSpansStore {
+                    stash: target.clone(),
+                })
+                .collect();
+            let (controllers, statuses, spans) = join3(
+                fetch_many(client, &bonded, at),
+                validator_statuses(client, &nominations.targets, progress.active_era, at),
+                fetch_many(client, &spans, at),
             )
-        })?;
-    let mut props = client.properties().clone();
-    let mut good_symbol = true;
-    for i in props.token_symbol.bytes() {
-        good_symbol &= i.is_ascii_uppercase()
-    }
-    if !good_symbol {
-        props.token_symbol = "".to_owned()
-    }
+            .await;
+            let submitted_in = nominations.submitted_in;
+            let targets = statuses?
+                .into_iter()
+                .zip(controllers?)
+                .zip(spans?)
+                .map(|(((status, prefs), controller), spans)| TargetInfo {
+                    status,
+                    prefs,
+                    controller,
+                    slashed_in: spans
+                        .map(|spans| spans.last_nonzero_slash)
+                        .filter(|&era| era > submitted_in),
+                })
+                .collect();
+            Some((nominations, targets))
+        }
+    };
+    Ok(NominatorInfo {
+        ledger,
+        payee,
+        progress,
+        nominations,
+    })
+}
 
-    println!(
-        "Nominator account: {}\nStash balance: {} {sym}\nAmount at stake: {} {sym}\nAmount \
-         unlocking: {:?} {sym}\nPayee: {:?}",
-        stash.to_ss58check_with_version(network),
-        pad(props.token_decimals, total),
-        pad(props.token_decimals, active),
-        unlocking,
+/// Format the status of the nominator controlled by `controller` at block `at`
+async fn display_nominators(
+    controller: AccountId,
+    client: &Client<KusamaRuntime>,
+    network: Ss58AddressFormat,
+    at: At<'_>,
+) -> Result<String, Error> {
+    let NominatorInfo {
+        ledger:
+            StakingLedger {
+                stash,
+                total,
+                active,
+                unlocking,
+                claimed_rewards: _, // not updated for nominators
+            },
         payee,
-        sym = props.token_symbol,
+        progress,
+        nominations,
+    } = fetch_nominator_info(&controller, client, at).await?;
+    let properties = client.properties();
+    let mut output = format!(
+        "Nominator account: {}\nStash balance: {}\nAmount at stake: {}\n{}Payee: {}\n",
+        stash.to_ss58check_with_version(network),
+        Balance::new(total, properties),
+        Balance::new(active, properties),
+        format_unlocking(&unlocking, &progress, properties, ""),
+        format_reward_destination(&payee, network),
     );
-    let nominations = match client.fetch(&NominatorsStore { stash }, None).await? {
+    let (nominations, targets) = match nominations {
         None => {
-            println!("Nominations: None (yet)");
-            return Ok(())
+            output += "Nominations: None (yet)\n";
+            return Ok(output)
         }
         Some(n) => n,
     };
-    println!(
+    writeln!(
+        output,
         "Era nominations submitted: {}\nNominations suppressed: {}\nTargets:\n",
         nominations.submitted_in, nominations.suppressed
-    );
-    for stash in nominations.targets.iter().cloned() {
-        let bonded = BondedStore {
-            stash: stash.clone(),
-        };
-        if let Some(controller) = client.fetch(&bonded, None).await? {
-            crate::common::display_validators(client, &[controller], network).await?
-        } else {
-            println!(
+    )?;
+    let controllers: Vec<_> = targets
+        .iter()
+        .filter_map(|info| info.controller.clone())
+        .collect();
+    let mut validators = crate::common::display_each_validator(client, &controllers, network, at)
+        .await?
+        .into_iter();
+    for (target, info) in nominations.targets.iter().zip(targets) {
+        let validator = info.controller.as_ref().and_then(|_| validators.next());
+        output += &format_target_status(target, &stash, progress.active_era, info, client, network);
+        match validator {
+            Some(validator) => output += &validator,
+            None => writeln!(
+                output,
                 "controller not found for stash {}\n",
-                stash.to_ss58check_with_version(network)
-            )
+                target.to_ss58check_with_version(network)
+            )?,
         }
     }
+    Ok(output)
+}
+
+/// The status of a nominator, as JSON
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct NominatorJson {
+    controller: String,
+    stash: String,
+    total: Amount,
+    active: Amount,
+    unlocking: Vec<UnlockChunkJson>,
+    /// The reward destination, in the form accepted by `set-payee`
+    payee: String,
+    /// The nominations, or `null` if the nominator is not nominating
+    nominations: Option<NominationsJson>,
+}
+
+/// The nominations of a nominator, as JSON
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+struct NominationsJson {
+    submitted_in: EraIndex,
+    suppressed: bool,
+    targets: Vec<TargetJson>,
+}
+
+/// A nomination target, as JSON
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+struct TargetJson {
+    stash: String,
+    /// `active`, `waiting`, or `chilled`, in the active era
+    status: &'static str,
+    /// The stake of the nominator backing the target in the active era, or
+    /// `null` if the target is not backed by it
+    backing: Option<Amount>,
+    /// Whether the target blocks new nominations
+    blocked: bool,
+    /// The era in which the target was slashed, if that makes the runtime
+    /// ignore the nomination
+    slashed_in: Option<EraIndex>,
+    /// The status of the target, or `null` if it has no controller
+    validator: Option<ValidatorJson>,
+}
+
+/// Fetch the status of the nominator controlled by `controller` at block `at`
+/// as JSON
+async fn nominator_json(
+    controller: AccountId,
+    client: &Client<KusamaRuntime>,
+    network: Ss58AddressFormat,
+    at: At<'_>,
+) -> Result<NominatorJson, Error> {
+    let NominatorInfo {
+        ledger,
+        payee,
+        progress,
+        nominations,
+    } = fetch_nominator_info(&controller, client, at).await?;
+    let properties = client.properties();
+    let nominations = match nominations {
+        None => None,
+        Some((nominations, targets)) => {
+            let controllers: Vec<_> = targets
+                .iter()
+                .filter_map(|info| info.controller.clone())
+                .collect();
+            let mut validators = validators_json(client, &controllers, network, at)
+                .await?
+                .into_iter();
+            let targets = nominations
+                .targets
+                .iter()
+                .zip(targets)
+                .map(|(target, info)| {
+                    let validator = info.controller.as_ref().and_then(|_| validators.next());
+                    let (status, backing) = match info.status {
+                        ValidatorStatus::Active(exposure) => (
+                            "active",
+                            exposure
+                                .others
+                                .iter()
+                                .find(|individual| individual.who == ledger.stash)
+                                .map(|individual| Amount::new(individual.value, properties)),
+                        ),
+                        ValidatorStatus::Waiting => ("waiting", None),
+                        ValidatorStatus::Chilled => ("chilled", None),
+                    };
+                    TargetJson {
+                        stash: target.to_ss58check_with_version(network),
+                        status,
+                        backing,
+                        blocked: info.prefs.map_or(false, |prefs| prefs.blocked),
+                        slashed_in: info.slashed_in,
+                        validator,
+                    }
+                })
+                .collect();
+            Some(NominationsJson {
+                submitted_in: nominations.submitted_in,
+                suppressed: nominations.suppressed,
+                targets,
+            })
+        }
+    };
+    Ok(NominatorJson {
+        controller: controller.to_ss58check_with_version(network),
+        stash: ledger.stash.to_ss58check_with_version(network),
+        total: Amount::new(ledger.total, properties),
+        active: Amount::new(ledger.active, properties),
+        unlocking: unlocking_json(&ledger.unlocking, &progress, properties),
+        payee: payee_code(&payee, network),
+        nominations,
+    })
+}
+
+/// Format whether `target` is in the active set in era `era`, how much of the
+/// stake of `nominator` backs it, whether it is blocking new nominations, and
+/// whether it was slashed after the nominations were submitted.
+fn format_target_status(
+    target: &AccountId,
+    nominator: &AccountId,
+    era: EraIndex,
+    info: TargetInfo,
+    client: &Client<KusamaRuntime>,
+    network: Ss58AddressFormat,
+) -> String {
+    let status = match info.status {
+        ValidatorStatus::Active(exposure) => {
+            match exposure.others.iter().find(|i| i.who == *nominator) {
+                Some(individual) => format!(
+                    "active in era {}, backed by {} of our stake",
+                    era,
+                    Balance::new(individual.value, client.properties())
+                ),
+                None => format!("active in era {}, not backed by our stake", era),
+            }
+        }
+        ValidatorStatus::Waiting => "waiting (not in the active set)".to_owned(),
+        ValidatorStatus::Chilled => "chilled (not intending to validate)".to_owned(),
+    };
+    let blocked = match info.prefs {
+        Some(ValidatorPrefs { blocked: true, .. }) => ", blocking new nominations",
+        _ => "",
+    };
+    let slashed = match info.slashed_in {
+        Some(era) => format!(
+            ", slashed in era {} (this nomination is ignored until it is submitted again)",
+            era
+        ),
+        None => String::new(),
+    };
+    format!(
+        "Target {}: {}{}{}\n",
+        target.to_ss58check_with_version(network),
+        status,
+        blocked,
+        slashed
+    )
+}
+
+/// The maximum number of nominations, from the chain metadata if it is there
+fn max_nominations(client: &Client<KusamaRuntime>) -> usize {
+    use std::convert::TryFrom;
+    client
+        .metadata()
+        .module("Staking")
+        .and_then(|module| module.constant("MaxNominations"))
+        .and_then(|constant| constant.value::<u32>())
+        .ok()
+        .and_then(|max| usize::try_from(max).ok())
+        .unwrap_or(MAX_NOMINATIONS)
+}
+
+/// Print how `targets` differs from the nominations of `controller` at block
+/// `at`
+async fn display_nomination_diff(
+    client: &Client<KusamaRuntime>,
+    controller: &AccountId,
+    targets: &[(AccountId, Option<String>)],
+    network: Ss58AddressFormat,
+    at: At<'_>,
+) -> Result<(), Error> {
+    let store = LedgerStore {
+        controller: controller.clone(),
+    };
+    let current = match fetch(client, &store, at).await? {
+        Some(StakingLedger { stash, .. }) => fetch(client, &NominatorsStore { stash }, at)
+            .await?
+            .map(|nominations| nominations.targets)
+            .unwrap_or_default(),
+        None => vec![],
+    };
+    let display = |target: &AccountId, label: &Option<String>| match label {
+        Some(label) => format!("{} ({})", target.to_ss58check_with_version(network), label),
+        None => target.to_ss58check_with_version(network),
+    };
+    println!("Added:");
+    for (target, label) in targets.iter().filter(|(i, _)| !current.contains(i)) {
+        println!("    + {}", display(target, label))
+    }
+    println!("Removed:");
+    for target in current
+        .iter()
+        .filter(|&i| !targets.iter().any(|(j, _)| i == j))
+    {
+        println!("    - {}", display(target, &None))
+    }
+    println!("Kept:");
+    for (target, label) in targets.iter().filter(|(i, _)| current.contains(i)) {
+        println!("      {}", display(target, label))
+    }
     Ok(())
 }
 
+/// Check that every nomination in `nominations` is on the signed allowlist at
+/// `allowlist`, so that the operator does not need to wait for the device to
+/// reject the transaction.
+fn check_allowlist(
+    nominations: &[AccountId],
+    allowlist: &Path,
+    allowlist_key: &Path,
+    network: Ss58AddressFormat,
+) -> Result<(), Error> {
+    let (key, key_network) = parse_public(&*fs::read(allowlist_key)?)?;
+    if key_network != network {
+        return Err(format!(
+            "Allowlist key is for network {}, not {}",
+            String::from(key_network),
+            String::from(network)
+        )
+        .into())
+    }
+    let file = BufReader::new(fs::File::open(allowlist)?);
+    let inspected = inspect_allowlist::<_, AccountId>(file, network, &key)?;
+    check_listed(nominations, &inspected, network)
+}
+
+/// Check that every nomination in `nominations` is in `inspected`, the
+/// output of [`inspect_allowlist`]: a `Nonce:` line followed by the allowed
+/// addresses
+fn check_listed(
+    nominations: &[AccountId],
+    inspected: &[String],
+    network: Ss58AddressFormat,
+) -> Result<(), Error> {
+    let allowed = inspected
+        .iter()
+        .filter(|line| !line.starts_with("Nonce:"))
+        .map(|address| Ok(parse_address::<AccountId>(address)?.0))
+        .collect::<Result<HashSet<_>, Error>>()?;
+    let rejected: Vec<_> = nominations
+        .iter()
+        .filter(|&stash| !allowed.contains(stash))
+        .map(|stash| stash.to_ss58check_with_version(network))
+        .collect();
+    if rejected.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "The following nominations are not on the allowlist, so the device would reject them: \
+             {}",
+            rejected.join(", ")
+        )
+        .into())
+    }
+}
+
 pub(crate) async fn main<T: FnOnce() -> Result<super::HardStore, Error>>(
     cmd: Nominator,
     client: Pin<Box<dyn Future<Output = Result<Client<KusamaRuntime>, Error>>>>,
     network: Ss58AddressFormat,
     keystore: T,
+    output: OutputFormat,
+    at: At<'_>,
 ) -> Result<Option<H256>, Error> {
     use std::convert::{TryFrom, TryInto};
     match cmd {
@@ -147,35 +618,77 @@ pub(crate) async fn main<T: FnOnce() -> Result<super::HardStore, Error>>(
         } => {
             validate_network("", provided_network, network)?;
             let client = client.await?;
-            let controller = match client.fetch(&BondedStore { stash }, None).await? {
+            let controller = match fetch(&client, &BondedStore { stash }, at).await? {
                 Some(controller) => controller,
                 None => return Err("Controller not found for stash".to_owned().into()),
             };
-            display_nominators(controller, &client, network).await?;
+            match output {
+                OutputFormat::Json => print_show_json(&client, &[controller], network, at).await?,
+                OutputFormat::Text => {
+                    print!(
+                        "{}",
+                        display_show(&client, &[controller], network, at).await?
+                    )
+                }
+            }
             Ok(None)
         }
-        Nominator::Show { index } => {
+        Nominator::Show { index, watch } => {
             let client = client.await?;
             let nominators = crate::common::fetch_validators(
                 &client,
                 crate::AddressSource::Device(index, &keystore()?),
                 network,
                 AccountType::Nominator,
+                at,
             )
             .await?;
-            for controller in nominators {
-                display_nominators(controller, &client, network).await?
+            let (client, nominators) = (&client, &*nominators);
+            let render = move |hash| display_show(client, nominators, network, At { hash, ..at });
+            if watch {
+                crate::watch::watch(client, at.rpc, nominators, network, render).await?
+            } else if output == OutputFormat::Json {
+                print_show_json(client, nominators, network, at).await?
+            } else {
+                print!("{}", render(at.hash).await?)
             }
             Ok(None)
         }
 
-        Nominator::Nominate { index, set } => {
-            let path = LedgeracioPath::new(network, AccountType::Nominator, index)?;
-            let signer = keystore()?.signer(path).await?;
-            if set.is_empty() {
-                return Err("Validator set cannot be empty".to_owned().into())
+        Nominator::Payouts { index } => {
+            let client = client.await?;
+            let nominators = crate::common::fetch_validators(
+                &client,
+                crate::AddressSource::Device(index, &keystore()?),
+                network,
+                AccountType::Nominator,
+                at,
+            )
+            .await?;
+            if output == OutputFormat::Json {
+                let nominators = nominators.into_iter().map(|controller| {
+                    crate::payouts::nominator_payouts_json(controller, &client, network, at)
+                });
+                print_json(&try_join_all(nominators).await?)?;
+                return Ok(None)
             }
-            let mut new_set = vec![];
+            let nominators = nominators.into_iter().map(|controller| {
+                crate::payouts::display_nominator_payouts(controller, &client, network, at)
+            });
+            for nominator in try_join_all(nominators).await? {
+                print!("{}", nominator)
+            }
+            Ok(None)
+        }
+
+        Nominator::Nominate {
+            index,
+            set,
+            targets_file,
+            allowlist,
+            allowlist_key,
+        } => {
+            let mut targets = vec![];
             for (address, provided_network) in set {
                 if network != provided_network.try_into().unwrap() {
                     return Err(format!(
@@ -187,9 +700,43 @@ pub(crate) async fn main<T: FnOnce() -> Result<super::HardStore, Error>>(
                     )
                     .into())
                 }
-                new_set.push(address)
+                targets.push((address, None))
+            }
+            if let Some(targets_file) = targets_file {
+                let file = BufReader::new(fs::File::open(targets_file)?);
+                targets.extend(parse_targets(file, network)?)
+            }
+            if targets.is_empty() {
+                return Err("Validator set cannot be empty".to_owned().into())
+            }
+            let mut seen = HashSet::new();
+            for (target, _) in &targets {
+                if !seen.insert(target) {
+                    return Err(format!(
+                        "Duplicate nomination {}",
+                        target.to_ss58check_with_version(network)
+                    )
+                    .into())
+                }
             }
-            Ok(Some(client.await?.nominate(&signer, new_set).await?))
+            let new_set: Vec<AccountId> = targets.iter().map(|(i, _)| i.clone()).collect();
+            if let (Some(allowlist), Some(allowlist_key)) = (allowlist, allowlist_key) {
+                check_allowlist(&new_set, &allowlist, &allowlist_key, network)?
+            }
+            let client = client.await?;
+            let max_nominations = max_nominations(&client);
+            if new_set.len() > max_nominations {
+                return Err(format!(
+                    "Too many nominations: {} given, but at most {} are allowed",
+                    new_set.len(),
+                    max_nominations
+                )
+                .into())
+            }
+            let path = LedgeracioPath::new(network, AccountType::Nominator, index)?;
+            let signer = keystore()?.signer(path).await?;
+            display_nomination_diff(&client, signer.account_id(), &targets, network, at).await?;
+            Ok(Some(client.nominate(&signer, new_set).await?))
         }
         Nominator::Chill { index } => {
             let path = LedgeracioPath::new(network, AccountType::Nominator, index)?;
@@ -197,13 +744,56 @@ pub(crate) async fn main<T: FnOnce() -> Result<super::HardStore, Error>>(
             Ok(Some(client.await?.chill(&signer).await?))
         }
         Nominator::SetPayee { index, target } => {
+            let target = validate_reward_destination(target, network)?;
+            println!(
+                "Setting payee to {}",
+                format_reward_destination(&target, network)
+            );
             let path = LedgeracioPath::new(network, AccountType::Nominator, index)?;
             let signer = keystore()?.signer(path).await?;
             Ok(Some(client.await?.set_payee(&signer, target).await?))
         }
         Nominator::Address { index } => {
-            crate::display_path(AccountType::Nominator, &keystore()?, network, index).await?;
+            crate::display_path(AccountType::Nominator, &keystore()?, network, index, output)
+                .await?;
             Ok(None)
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LISTED: &str = "5DArCreQ9Yk2HaGvxcRHS35qky3eXBD5BprPZQvbiJBfFY6Y";
+    const UNLISTED: &str = "5GQvjFcJBCGTFeb2hvtQ9yRfbDNQajLJbW1yzgCra5uUTLvn";
+
+    fn inspected() -> Vec<String> { vec!["Nonce: 3\n".to_owned(), LISTED.to_owned()] }
+
+    fn account(address: &str) -> AccountId { AccountId::from_ss58check(address).unwrap() }
+
+    #[test]
+    fn listed_nominations_are_allowed() {
+        let network = Ss58AddressFormat::SubstrateAccount;
+        check_listed(&[account(LISTED)], &inspected(), network).unwrap();
+        check_listed(&[], &inspected(), network).unwrap();
+    }
+
+    #[test]
+    fn unlisted_nominations_are_rejected() {
+        let network = Ss58AddressFormat::SubstrateAccount;
+        let error = check_listed(&[account(LISTED), account(UNLISTED)], &inspected(), network)
+            .unwrap_err()
+            .to_string();
+        assert!(error.ends_with(UNLISTED), "{}", error);
+        assert!(!error.contains(LISTED), "{}", error);
+    }
+
+    #[test]
+    fn nonce_is_not_an_address() {
+        // Only the nonce line is skipped, wherever it is.
+        let network = Ss58AddressFormat::SubstrateAccount;
+        let inspected = [LISTED.to_owned(), "Nonce: 0\n".to_owned()];
+        check_listed(&[account(LISTED)], &inspected, network).unwrap();
+    }
+}
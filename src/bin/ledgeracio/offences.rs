@@ -0,0 +1,58 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of ledgeracio.
+//
+// ledgeracio is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// ledgeracio is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ledgeracio.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Storage items of the Offences pallet, which `substrate_subxt` does not
+//! provide
+
+use crate::staking::Exposure;
+use codec::{Decode, Encode};
+use std::marker::PhantomData;
+use substrate_subxt::{system::System, Store};
+
+const MODULE: &str = "Offences";
+
+/// The kind of an offence, such as `im-online:offlin`
+pub type Kind = [u8; 16];
+
+/// A reported offence
+#[derive(PartialEq, Eq, Clone, Encode, Decode, Debug)]
+pub struct OffenceDetails<AccountId> {
+    /// The stash account of the offending validator, and its exposure at the
+    /// time of the offence
+    pub offender: (AccountId, Exposure<AccountId, u128>),
+    /// The reporters of the offence
+    pub reporters: Vec<AccountId>,
+}
+
+/// The details of an offence, keyed by report ID
+#[derive(Clone, Encode, Decode, Debug, Store)]
+pub struct ReportsStore<T: System> {
+    #[store(returns = OffenceDetails<T::AccountId>)]
+    /// The report ID
+    pub report_id: T::Hash,
+}
+
+/// The IDs of the reports of offences of the same kind in the same time slot
+#[derive(Clone, Encode, Decode, Debug, Store)]
+pub struct ConcurrentReportsIndexStore<T: System> {
+    #[store(returns = Vec<T::Hash>)]
+    /// The kind of the offences
+    pub kind: Kind,
+    /// The encoded time slot of the offences
+    pub time_slot: Vec<u8>,
+    /// Runtime marker
+    pub _runtime: PhantomData<T>,
+}
@@ -0,0 +1,97 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of ledgeracio.
+//
+// ledgeracio is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// ledgeracio is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ledgeracio.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Machine-readable output.  The schemas are documented in FORMATS.md, and
+//! must not change incompatibly.
+
+use super::Error;
+use serde::Serialize;
+use std::str::FromStr;
+use substrate_subxt::SystemProperties;
+
+/// The format of the output of read-only commands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum OutputFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &*s.to_ascii_lowercase() {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(format!(
+                "Invalid output format {}: expected `text` or `json`",
+                s
+            )),
+        }
+    }
+}
+
+/// An amount of the chain’s token
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Amount {
+    /// The amount in planck, as a decimal string, since it may not fit in a
+    /// JSON number
+    planck: String,
+    /// The number of decimal places of the token
+    decimals: u8,
+    /// The token symbol, such as `KSM`
+    symbol: String,
+}
+
+impl Amount {
+    pub(crate) fn new(planck: u128, properties: &SystemProperties) -> Self {
+        Self {
+            planck: planck.to_string(),
+            decimals: properties.token_decimals,
+            symbol: properties.token_symbol.clone(),
+        }
+    }
+}
+
+/// Print `value` as JSON on stdout
+pub(crate) fn print_json<T: Serialize>(value: &T) -> Result<(), Error> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// Format an error as a JSON object, for stderr
+pub(crate) fn error_json(error: &str) -> String {
+    serde_json::json!({ "error": error }).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_works() {
+        let properties = SystemProperties {
+            ss58_format: 2,
+            token_decimals: 12,
+            token_symbol: "KSM".to_owned(),
+        };
+        assert_eq!(
+            serde_json::to_string(&Amount::new(u128::max_value(), &properties)).unwrap(),
+            r#"{"planck":"340282366920938463463374607431768211455","decimals":12,"symbol":"KSM"}"#
+        );
+        assert_eq!(error_json("bad \"thing\""), r#"{"error":"bad \"thing\""}"#);
+    }
+}
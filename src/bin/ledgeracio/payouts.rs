@@ -16,43 +16,204 @@
 
 //! Payouts handling
 
-use crate::Error;
-use futures::{future::join3,
-              stream::{FuturesUnordered, StreamExt as _}};
+use crate::{common::{fetch, fetch_chunked, fetch_many, fetch_or_default, At},
+            output::Amount,
+            staking::{EraIndex, ErasStakersClippedStore, ErasStakersStore,
+                      ErasValidatorPrefsStore, ErasValidatorRewardStore, PayoutStakersCall},
+            utility::BatchCall,
+            Error};
+use codec::Decode;
+use futures::future::join3;
+use ledgeracio::{Balance, HardSigner};
 use log::trace;
-use std::marker::PhantomData;
-use substrate_subxt::{sp_core::crypto::AccountId32 as AccountId,
-                      sp_runtime::traits::Zero,
-                      staking::{CurrentEraStore, ErasRewardPointsStore, HistoryDepthStore,
-                                LedgerStore, StakingLedger},
-                      Client, KusamaRuntime};
-
-pub(crate) async fn display_payouts(
+use serde::Serialize;
+use std::{cmp::Ordering, collections::HashMap, marker::PhantomData};
+use substrate_subxt::{sp_core::crypto::{AccountId32 as AccountId, Ss58AddressFormat, Ss58Codec},
+                      sp_runtime::{traits::Zero, Perbill},
+                      staking::{BondedStore, CurrentEraStore, EraRewardPoints,
+                                ErasRewardPointsStore, HistoryDepthStore, LedgerStore,
+                                StakingLedger},
+                      Client, KusamaRuntime, Signer, SystemProperties};
+
+/// Warn about payouts that will expire in fewer than this many eras
+pub(crate) const EXPIRY_WARNING_ERAS: EraIndex = 8;
+
+/// Unclaimed validator payouts
+pub(crate) struct Payouts {
+    /// The stash account of the validator
+    pub(crate) stash: AccountId,
+    /// The oldest era for which payouts can still be claimed
+    pub(crate) history_start: EraIndex,
+    /// The eras with unclaimed payouts, oldest first
+    pub(crate) eras: Vec<UnclaimedEra>,
+}
+
+/// An era with an unclaimed payout
+#[derive(Clone, Copy)]
+pub(crate) struct UnclaimedEra {
+    /// The era
+    pub(crate) era: EraIndex,
+    /// The estimated reward, or `None` if the era has not yet ended
+    pub(crate) reward: Option<EstimatedReward>,
+}
+
+/// The estimated reward of a validator and its nominators for an era, in
+/// planck
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) struct EstimatedReward {
+    /// The reward to the validator, including its commission
+    pub(crate) validator: u128,
+    /// The reward to all of the nominators of the validator
+    pub(crate) nominators: u128,
+}
+
+/// A validator that backed a nominator in an era, but has not claimed the
+/// payout for that era
+pub(crate) struct NominatorPayout {
+    /// The era
+    pub(crate) era: EraIndex,
+    /// The stash account of the validator
+    pub(crate) validator: AccountId,
+    /// The estimated share of the nominator, in planck, or `None` if the era
+    /// has not yet ended
+    pub(crate) share: Option<u128>,
+    /// Whether the nominator was left out of the clipped exposure of the
+    /// validator, and so will not be paid
+    pub(crate) oversubscribed: bool,
+}
+
+/// Split the reward of a validator for an era into its commission and the
+/// leftover that is shared by stake
+pub(crate) fn split_reward(
+    era_reward: u128,
+    points: u32,
+    total_points: u32,
+    commission: Perbill,
+) -> (u128, u128) {
+    let validator_total = Perbill::from_rational_approximation(points, total_points) * era_reward;
+    let commission = commission * validator_total;
+    (commission, validator_total.saturating_sub(commission))
+}
+
+/// Estimate the reward for an era, in the same way as the staking pallet
+/// computes it.  `era_reward` is the total reward for all validators,
+/// `points` and `total_points` are the validator’s and the total reward points,
+/// and `own` and `total` are the validator’s own and total exposure.
+pub(crate) fn estimate_reward(
+    era_reward: u128,
+    points: u32,
+    total_points: u32,
+    commission: Perbill,
+    own: u128,
+    total: u128,
+) -> EstimatedReward {
+    let (commission, leftover) = split_reward(era_reward, points, total_points, commission);
+    let own = Perbill::from_rational_approximation(own, total) * leftover;
+    EstimatedReward {
+        validator: commission.saturating_add(own),
+        nominators: leftover.saturating_sub(own),
+    }
+}
+
+/// Estimate the reward of a single nominator for an era.  The arguments are as
+/// for [`estimate_reward`], except that `value` is the nominator’s exposure.
+pub(crate) fn estimate_nominator_share(
+    era_reward: u128,
+    points: u32,
+    total_points: u32,
+    commission: Perbill,
+    value: u128,
+    total: u128,
+) -> u128 {
+    let (_, leftover) = split_reward(era_reward, points, total_points, commission);
+    Perbill::from_rational_approximation(value, total) * leftover
+}
+
+/// Format unclaimed validator payouts as a table
+pub(crate) fn format_payouts(payouts: &Payouts, properties: &SystemProperties) -> String {
+    if payouts.eras.is_empty() {
+        return "    Unclaimed payouts: none\n".to_owned()
+    }
+    let mut table = format!(
+        "    Unclaimed payouts:\n        {:<8}{:>28}{:>28}\n",
+        "Era", "Validator reward", "Nominator rewards"
+    );
+    for &UnclaimedEra { era, reward } in &payouts.eras {
+        table += &match reward {
+            Some(EstimatedReward {
+                validator,
+                nominators,
+            }) => format!(
+                "        {:<8}{:>28}{:>28}\n",
+                era,
+                Balance::new(validator, properties).to_string(),
+                Balance::new(nominators, properties).to_string()
+            ),
+            None => format!("        {:<8}{:>28}{:>28}\n", era, "(era in progress)", ""),
+        }
+    }
+    table
+}
+
+/// An era with an unclaimed validator payout, as JSON
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct UnclaimedEraJson {
+    era: EraIndex,
+    /// The estimated reward of the validator, including its commission, or
+    /// `null` if the era has not yet ended
+    validator_reward: Option<Amount>,
+    /// The estimated reward of all of the nominators, or `null` if the era
+    /// has not yet ended
+    nominator_rewards: Option<Amount>,
+}
+
+/// Convert unclaimed validator payouts to JSON
+pub(crate) fn payouts_json(
+    payouts: &Payouts,
+    properties: &SystemProperties,
+) -> Vec<UnclaimedEraJson> {
+    payouts
+        .eras
+        .iter()
+        .map(|&UnclaimedEra { era, reward }| UnclaimedEraJson {
+            era,
+            validator_reward: reward.map(|reward| Amount::new(reward.validator, properties)),
+            nominator_rewards: reward.map(|reward| Amount::new(reward.nominators, properties)),
+        })
+        .collect()
+}
+
+/// Find the eras in the history window in which the validator controlled by
+/// `controller` earned points, but has not claimed its payout as of block
+/// `at`.  The history window is that of the `CurrentEra` at `at`.
+pub(crate) async fn unclaimed_payouts(
     controller: AccountId,
     client: &Client<KusamaRuntime>,
-) -> Result<Vec<u32>, Error> {
+    at: At<'_>,
+) -> Result<Payouts, Error> {
     let store = LedgerStore {
         controller: controller.clone(),
     };
-    let history_depth = client.fetch_or_default(
+    let history_depth = fetch_or_default(
+        client,
         &HistoryDepthStore {
             _runtime: PhantomData,
         },
-        None,
+        at,
     );
-    let current_era = client.fetch_or_default(
+    let current_era = fetch_or_default(
+        client,
         &CurrentEraStore {
             _runtime: PhantomData,
         },
-        None,
+        at,
     );
     let fetch_account_info = async {
         let StakingLedger {
             stash,
             claimed_rewards,
             ..
-        } = client
-            .fetch(&store, None)
+        } = fetch(client, &store, at)
             .await?
             .ok_or_else(|| format!("No nominator account found for controller {}", controller))?;
         let res: Result<_, Error> = Ok((stash, claimed_rewards));
@@ -64,40 +225,575 @@ pub(crate) async fn display_payouts(
     let (validator_stash, claimed_rewards): (AccountId, _) = account_info?;
     let current_era = current_era?;
     let history_start = current_era.saturating_sub(history_depth);
-    let mut futures = FuturesUnordered::new();
     trace!("Claimed rewards: {:?}", claimed_rewards);
-    for era in history_start..=current_era {
-        if claimed_rewards.binary_search(&era).is_ok() {
-            continue
-        }
-        let validator_stash = validator_stash.clone();
-        let future = async move {
-            let rewards = ErasRewardPointsStore {
-                index: era,
-                _phantom: PhantomData,
-            };
-            let era_reward_points = client.fetch_or_default(&rewards, None).await?;
-            let s: Result<_, Error> = Ok((
-                era_reward_points
-                    .individual
-                    .get(&validator_stash)
-                    .cloned()
-                    .unwrap_or_else(Zero::zero),
-                era,
-            ));
-            s
-        };
-        futures.push(future);
-    }
-    let mut eras = vec![];
-    while let Some(e) = futures.next().await {
-        let (points, era) = e?;
+    let unclaimed: Vec<_> = (history_start..=current_era)
+        .filter(|era| claimed_rewards.binary_search(era).is_err())
+        .map(|index| ErasRewardPointsStore {
+            index,
+            _phantom: PhantomData,
+        })
+        .collect();
+    let mut earned = vec![];
+    for (store, era_reward_points) in unclaimed
+        .iter()
+        .zip(fetch_many(client, &unclaimed, at).await?)
+    {
+        let era = store.index;
+        let era_reward_points = era_reward_points.unwrap_or_default();
+        let points = era_reward_points
+            .individual
+            .get(&validator_stash)
+            .cloned()
+            .unwrap_or_else(Zero::zero);
         if points == 0 {
             trace!("Skipping era {} as it has no points", era);
         } else {
             trace!("Found {} points for era {}", points, era);
-            eras.push(era);
+            earned.push((era, points, era_reward_points.total))
+        }
+    }
+    let era_rewards: Vec<_> = earned
+        .iter()
+        .map(|&(era, ..)| ErasValidatorRewardStore {
+            era,
+            _runtime: PhantomData,
+        })
+        .collect();
+    let prefs: Vec<_> = earned
+        .iter()
+        .map(|&(era, ..)| ErasValidatorPrefsStore {
+            era,
+            stash: validator_stash.clone(),
+        })
+        .collect();
+    let exposures: Vec<_> = earned
+        .iter()
+        .map(|&(era, ..)| ErasStakersClippedStore {
+            era,
+            stash: validator_stash.clone(),
+        })
+        .collect();
+    let (era_rewards, prefs, exposures) = join3(
+        fetch_many(client, &era_rewards, at),
+        fetch_many(client, &prefs, at),
+        fetch_many(client, &exposures, at),
+    )
+    .await;
+    let eras = earned
+        .into_iter()
+        .zip(era_rewards?)
+        .zip(prefs?.into_iter().zip(exposures?))
+        .map(
+            |(((era, points, total_points), era_reward), (prefs, exposure))| {
+                let exposure = exposure.unwrap_or_default();
+                let reward = era_reward.map(|era_reward| {
+                    estimate_reward(
+                        era_reward,
+                        points,
+                        total_points,
+                        prefs.map_or(Perbill::zero(), |prefs| prefs.commission),
+                        exposure.own,
+                        exposure.total,
+                    )
+                });
+                UnclaimedEra { era, reward }
+            },
+        )
+        .collect();
+    Ok(Payouts {
+        stash: validator_stash,
+        history_start,
+        eras,
+    })
+}
+
+/// Find the unclaimed payouts of the nominator controlled by `controller`, and
+/// format them as a table.
+pub(crate) async fn display_nominator_payouts(
+    controller: AccountId,
+    client: &Client<KusamaRuntime>,
+    network: Ss58AddressFormat,
+    at: At<'_>,
+) -> Result<String, Error> {
+    let (stash, payouts) = unclaimed_nominator_payouts(controller, client, at).await?;
+    let mut output = format!(
+        "Nominator account: {}\n",
+        stash.to_ss58check_with_version(network)
+    );
+    if payouts.is_empty() {
+        output += "Unclaimed payouts: none\n";
+        return Ok(output)
+    }
+    let properties = client.properties();
+    output += &format!(
+        "Unclaimed payouts:\n    {:<8}{:<50}{:>28}\n",
+        "Era", "Validator", "Estimated share"
+    );
+    for NominatorPayout {
+        era,
+        validator,
+        share,
+        oversubscribed,
+    } in payouts
+    {
+        let share = match share {
+            _ if oversubscribed => "(oversubscribed)".to_owned(),
+            Some(share) => Balance::new(share, properties).to_string(),
+            None => "(era in progress)".to_owned(),
+        };
+        output += &format!(
+            "    {:<8}{:<50}{:>28}\n",
+            era,
+            validator.to_ss58check_with_version(network),
+            share
+        );
+    }
+    Ok(output)
+}
+
+/// The unclaimed payouts of a nominator, as JSON
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct NominatorPayoutsJson {
+    stash: String,
+    unclaimed_payouts: Vec<NominatorPayoutJson>,
+}
+
+/// A payout owed to a nominator, as JSON
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+struct NominatorPayoutJson {
+    era: EraIndex,
+    validator: String,
+    /// The estimated share of the nominator, or `null` if the era has not yet
+    /// ended
+    share: Option<Amount>,
+    /// Whether the nominator will not be paid, because the validator was
+    /// oversubscribed
+    oversubscribed: bool,
+}
+
+/// Find the payouts still owed to the nominator controlled by `controller`,
+/// as JSON
+pub(crate) async fn nominator_payouts_json(
+    controller: AccountId,
+    client: &Client<KusamaRuntime>,
+    network: Ss58AddressFormat,
+    at: At<'_>,
+) -> Result<NominatorPayoutsJson, Error> {
+    let (stash, payouts) = unclaimed_nominator_payouts(controller, client, at).await?;
+    let properties = client.properties();
+    Ok(NominatorPayoutsJson {
+        stash: stash.to_ss58check_with_version(network),
+        unclaimed_payouts: payouts
+            .into_iter()
+            .map(|payout| NominatorPayoutJson {
+                era: payout.era,
+                validator: payout.validator.to_ss58check_with_version(network),
+                share: payout.share.map(|share| Amount::new(share, properties)),
+                oversubscribed: payout.oversubscribed,
+            })
+            .collect(),
+    })
+}
+
+/// The reward points of each era
+pub(crate) type ErasPoints = HashMap<EraIndex, EraRewardPoints<AccountId>>;
+
+/// The validators that earned reward points in each of `eras` at block `at`,
+/// oldest era first, along with the reward points of each era.  Validators
+/// without points are not paid, so only these can owe payouts for those eras.
+pub(crate) async fn rewarded_validators(
+    client: &Client<KusamaRuntime>,
+    eras: &[EraIndex],
+    at: At<'_>,
+) -> Result<(Vec<(EraIndex, AccountId)>, ErasPoints), Error> {
+    let stores: Vec<_> = eras
+        .iter()
+        .map(|&index| ErasRewardPointsStore {
+            index,
+            _phantom: PhantomData,
+        })
+        .collect();
+    let points: HashMap<_, _> = eras
+        .iter()
+        .copied()
+        .zip(fetch_many(client, &stores, at).await?)
+        .filter_map(|(era, points)| Some((era, points?)))
+        .collect();
+    let mut rewarded = vec![];
+    for era in eras {
+        if let Some(points) = points.get(era) {
+            rewarded.extend(
+                points
+                    .individual
+                    .iter()
+                    .filter(|(_, points)| **points != 0)
+                    .map(|(validator, _)| (*era, validator.clone())),
+            )
+        }
+    }
+    Ok((rewarded, points))
+}
+
+/// The eras for which each of `validators` has claimed its payout, as of
+/// block `at`.  Validators that are no longer bonded are left out, as they
+/// cannot be paid out.
+pub(crate) async fn claimed_eras(
+    client: &Client<KusamaRuntime>,
+    validators: &[AccountId],
+    at: At<'_>,
+) -> Result<HashMap<AccountId, Vec<EraIndex>>, Error> {
+    let bonded: Vec<_> = validators
+        .iter()
+        .map(|stash| BondedStore {
+            stash: stash.clone(),
+        })
+        .collect();
+    let ledgers: Vec<_> = fetch_chunked(client, &bonded, at)
+        .await?
+        .into_iter()
+        .flatten()
+        .map(|controller| LedgerStore { controller })
+        .collect();
+    Ok(fetch_chunked(client, &ledgers, at)
+        .await?
+        .into_iter()
+        .flatten()
+        .map(|ledger| (ledger.stash, ledger.claimed_rewards))
+        .collect())
+}
+
+/// Find the validators that backed `nominator` in each of `eras`, earned
+/// points, and have not claimed their payouts, as of block `at`.  Every
+/// validator that earned points is checked, not only those the nominator
+/// nominates now.  The reward points of each era are returned as well.
+async fn unclaimed_backers(
+    client: &Client<KusamaRuntime>,
+    nominator: &AccountId,
+    eras: &[EraIndex],
+    at: At<'_>,
+) -> Result<(Vec<(EraIndex, AccountId)>, ErasPoints), Error> {
+    let (rewarded, era_points) = rewarded_validators(client, eras, at).await?;
+    // `claimed_rewards` is only updated in the ledgers of validators.
+    let mut validators: Vec<_> = rewarded
+        .iter()
+        .map(|(_, validator)| validator.clone())
+        .collect();
+    validators.sort_unstable();
+    validators.dedup();
+    let claimed = claimed_eras(client, &validators, at).await?;
+    trace!("Claimed rewards: {:?}", claimed);
+    let unclaimed: Vec<_> = rewarded
+        .into_iter()
+        .filter(|(era, validator)| {
+            claimed
+                .get(validator)
+                .map_or(false, |claimed| claimed.binary_search(era).is_err())
+        })
+        .map(|(era, validator)| ErasStakersStore {
+            era,
+            stash: validator,
+        })
+        .collect();
+    let backed: Vec<(EraIndex, AccountId)> = unclaimed
+        .iter()
+        .zip(fetch_chunked(client, &unclaimed, at).await?)
+        .filter(|(_, exposure)| {
+            exposure.as_ref().map_or(false, |exposure| {
+                exposure
+                    .others
+                    .iter()
+                    .any(|individual| individual.who == *nominator)
+            })
+        })
+        .map(|(store, _)| (store.era, store.stash.clone()))
+        .collect();
+    Ok((backed, era_points))
+}
+
+/// Find the eras in the history window in which a validator backed the
+/// nominator controlled by `controller`, earned points, and has not claimed
+/// its payout, as of block `at`.  This reads the exposure of every validator
+/// that earned points in an era it has not claimed.  Returns the stash account
+/// of the nominator along with the payouts, oldest first.
+pub(crate) async fn unclaimed_nominator_payouts(
+    controller: AccountId,
+    client: &Client<KusamaRuntime>,
+    at: At<'_>,
+) -> Result<(AccountId, Vec<NominatorPayout>), Error> {
+    let store = LedgerStore {
+        controller: controller.clone(),
+    };
+    let history_depth = fetch_or_default(
+        client,
+        &HistoryDepthStore {
+            _runtime: PhantomData,
+        },
+        at,
+    );
+    let current_era = fetch_or_default(
+        client,
+        &CurrentEraStore {
+            _runtime: PhantomData,
+        },
+        at,
+    );
+    let (history_depth, ledger, current_era) =
+        join3(history_depth, fetch(client, &store, at), current_era).await;
+    let StakingLedger { stash, .. } = ledger?
+        .ok_or_else(|| format!("No nominator account found for controller {}", controller))?;
+    let current_era = current_era?;
+    let history_start = current_era.saturating_sub(history_depth?);
+    let eras: Vec<_> = (history_start..=current_era).collect();
+    let (backed, era_points) = unclaimed_backers(client, &stash, &eras, at).await?;
+    let mut eras: Vec<EraIndex> = backed.iter().map(|&(era, _)| era).collect();
+    eras.dedup();
+    let era_rewards: Vec<_> = eras
+        .iter()
+        .map(|&era| ErasValidatorRewardStore {
+            era,
+            _runtime: PhantomData,
+        })
+        .collect();
+    let prefs: Vec<_> = backed
+        .iter()
+        .map(|(era, validator)| ErasValidatorPrefsStore {
+            era: *era,
+            stash: validator.clone(),
+        })
+        .collect();
+    let exposures: Vec<_> = backed
+        .iter()
+        .map(|(era, validator)| ErasStakersClippedStore {
+            era: *era,
+            stash: validator.clone(),
+        })
+        .collect();
+    let (era_rewards, prefs, exposures) = join3(
+        fetch_many(client, &era_rewards, at),
+        fetch_chunked(client, &prefs, at),
+        fetch_chunked(client, &exposures, at),
+    )
+    .await;
+    let era_rewards: HashMap<_, _> = eras.into_iter().zip(era_rewards?).collect();
+    let mut payouts = vec![];
+    for ((era, validator), (prefs, exposure)) in
+        backed.into_iter().zip(prefs?.into_iter().zip(exposures?))
+    {
+        let era_reward = era_rewards[&era];
+        let (points, total_points) = era_points.get(&era).map_or((0, 0), |points| {
+            let individual = points
+                .individual
+                .get(&validator)
+                .cloned()
+                .unwrap_or_else(Zero::zero);
+            (individual, points.total)
+        });
+        if points == 0 {
+            trace!("Skipping era {} of {} as it has no points", era, validator);
+            continue
         }
+        // Only the nominators in the clipped exposure are paid.
+        let exposure = exposure.unwrap_or_default();
+        let value = exposure
+            .others
+            .iter()
+            .find(|individual| individual.who == stash)
+            .map(|individual| individual.value);
+        let share = era_reward.map(|era_reward| {
+            estimate_nominator_share(
+                era_reward,
+                points,
+                total_points,
+                prefs.map_or(Perbill::zero(), |prefs| prefs.commission),
+                value.unwrap_or_default(),
+                exposure.total,
+            )
+        });
+        payouts.push(NominatorPayout {
+            era,
+            validator,
+            share,
+            oversubscribed: value.is_none(),
+        })
+    }
+    Ok((stash, payouts))
+}
+
+/// The payouts of a validator that are to be claimed
+pub(crate) struct Claim {
+    /// The stash account of the validator
+    stash: AccountId,
+    /// The eras to claim, oldest first
+    eras: Vec<EraIndex>,
+}
+
+/// Check the payouts of the validator controlled by `signer` for `eras`, or
+/// find all of its unclaimed eras if `eras` is empty, without claiming
+/// anything.  The unclaimed payouts are found at block `at`.  Fails if an era
+/// in `eras` has no claimable payout.
+pub(crate) async fn plan_payouts(
+    client: &Client<KusamaRuntime>,
+    signer: &HardSigner,
+    eras: &[EraIndex],
+    network: Ss58AddressFormat,
+    at: At<'_>,
+) -> Result<Claim, Error> {
+    let Payouts {
+        stash,
+        history_start,
+        eras: unclaimed,
+    } = unclaimed_payouts(signer.account_id().clone(), client, at).await?;
+    // Eras that have not ended cannot be paid out yet.
+    let claimable: Vec<EraIndex> = unclaimed
+        .iter()
+        .filter(|unclaimed| unclaimed.reward.is_some())
+        .map(|unclaimed| unclaimed.era)
+        .collect();
+    let eras = if eras.is_empty() {
+        claimable
+    } else {
+        for era in eras {
+            if claimable.binary_search(era).is_err() {
+                return Err(format!(
+                    "Era {} has no claimable payout for validator {}",
+                    era,
+                    stash.to_ss58check_with_version(network)
+                )
+                .into())
+            }
+        }
+        let mut eras = eras.to_owned();
+        eras.sort_unstable();
+        eras.dedup();
+        eras
+    };
+    for &era in &eras {
+        let remaining = era - history_start;
+        if remaining < EXPIRY_WARNING_ERAS {
+            eprintln!(
+                "Warning: the payout for era {} will expire in {} era(s)",
+                era,
+                remaining + 1
+            )
+        }
+    }
+    Ok(Claim { stash, eras })
+}
+
+/// Claim the payouts in `claim`, made by [`plan_payouts`] for the validator
+/// controlled by `signer`.  The payouts are claimed oldest first, in a single
+/// batch if there is more than one.
+pub(crate) async fn claim_payouts(
+    client: &Client<KusamaRuntime>,
+    signer: &HardSigner,
+    claim: Claim,
+    network: Ss58AddressFormat,
+) -> Result<(), Error> {
+    let Claim { stash, eras } = claim;
+    if eras.is_empty() {
+        println!(
+            "No unclaimed payouts for validator {}",
+            stash.to_ss58check_with_version(network)
+        );
+        return Ok(())
+    }
+    println!(
+        "Claiming payouts of validator {} for eras {:?}",
+        stash.to_ss58check_with_version(network),
+        eras
+    );
+    let success = if let [era] = *eras {
+        let call = PayoutStakersCall {
+            validator_stash: stash,
+            era,
+        };
+        client.watch(call, signer).await?
+    } else {
+        let mut calls = vec![];
+        for &era in &eras {
+            calls.push(client.encode(PayoutStakersCall {
+                validator_stash: stash.clone(),
+                era,
+            })?)
+        }
+        client
+            .watch(
+                BatchCall {
+                    _runtime: PhantomData,
+                    calls,
+                },
+                signer,
+            )
+            .await?
+    };
+    println!(
+        "Transaction {:?} included in block {:?}",
+        success.extrinsic, success.block
+    );
+    // A batch stops at the first call that fails, and reports its index.
+    let failed = success
+        .find_event_raw("Utility", "BatchInterrupted")
+        .map(|event| u32::decode(&mut &*event.data))
+        .transpose()?
+        .map_or(eras.len(), |index| index as usize);
+    for (index, era) in eras.iter().enumerate() {
+        println!("    Era {}: {}", era, match index.cmp(&failed) {
+            Ordering::Less => "paid out",
+            Ordering::Equal => "failed",
+            Ordering::Greater => "not attempted",
+        })
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimate_reward_works() {
+        // All points, no commission, no nominators
+        assert_eq!(
+            estimate_reward(1000, 10, 10, Perbill::zero(), 100, 100),
+            EstimatedReward {
+                validator: 1000,
+                nominators: 0
+            }
+        );
+        // Half the points, 10% commission, a quarter of the stake
+        assert_eq!(
+            estimate_reward(2000, 5, 10, Perbill::from_percent(10), 25, 100),
+            EstimatedReward {
+                validator: 100 + 225,
+                nominators: 675
+            }
+        );
+        // 100% commission
+        assert_eq!(
+            estimate_reward(1000, 1, 1, Perbill::one(), 1, 100),
+            EstimatedReward {
+                validator: 1000,
+                nominators: 0
+            }
+        );
+    }
+
+    #[test]
+    fn estimate_nominator_share_works() {
+        // Half the points, 10% commission, a quarter of the stake
+        assert_eq!(
+            estimate_nominator_share(2000, 5, 10, Perbill::from_percent(10), 25, 100),
+            225
+        );
+        // 100% commission
+        assert_eq!(
+            estimate_nominator_share(1000, 1, 1, Perbill::one(), 99, 100),
+            0
+        );
+        // Not exposed at all
+        assert_eq!(
+            estimate_nominator_share(1000, 1, 1, Perbill::zero(), 0, 100),
+            0
+        );
     }
-    Ok(eras)
 }
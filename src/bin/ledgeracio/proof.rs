@@ -0,0 +1,616 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of ledgeracio.
+//
+// ledgeracio is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// ledgeracio is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ledgeracio.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Checking what the node returns against storage proofs.  The finalized
+//! chain is followed from a trusted checkpoint using GRANDPA justifications,
+//! so the state roots that the proofs are checked against do not depend on
+//! trusting the node.
+
+use crate::{rpc::{self, BlockId, Header},
+            Error};
+use codec::{Decode, Encode};
+use futures::future::try_join_all;
+use log::info;
+use serde::{Deserialize, Serialize};
+use sp_state_machine::{read_proof_check, StorageProof};
+use std::{collections::{HashMap, HashSet},
+          convert::TryFrom,
+          env, fs,
+          io::BufReader,
+          iter,
+          path::{Path, PathBuf}};
+use substrate_subxt::{sp_core::{crypto::Ss58AddressFormat,
+                                ed25519,
+                                storage::{StorageData, StorageKey},
+                                twox_128, Blake2Hasher, Bytes, Pair, H256},
+                      sp_runtime::{generic::DigestItem, traits::Header as _, ConsensusEngineId}};
+
+type BlockNumber = u32;
+
+/// The ID of GRANDPA in header digests
+const GRANDPA_ENGINE_ID: ConsensusEngineId = *b"FRNK";
+
+/// Polkadot and Kusama nodes keep the justification of every block with a
+/// number that is a multiple of this, as well as of every block that enacts
+/// a change of the GRANDPA authorities
+const JUSTIFICATION_PERIOD: BlockNumber = 512;
+
+/// The number of headers to request at once when following the chain
+const HEADER_BATCH: BlockNumber = 256;
+
+/// The storage key of the GRANDPA authorities
+const AUTHORITIES_KEY: &[u8] = b":grandpa_authorities";
+
+/// The version of the encoding of the value at [`AUTHORITIES_KEY`]
+const AUTHORITIES_VERSION: u8 = 1;
+
+/// A GRANDPA authority and its voting weight
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+struct Authority {
+    /// The Ed25519 public key of the authority
+    id: Bytes,
+    weight: u64,
+}
+
+impl From<(ed25519::Public, u64)> for Authority {
+    fn from((id, weight): (ed25519::Public, u64)) -> Self {
+        Self {
+            id: Bytes(id.0.to_vec()),
+            weight,
+        }
+    }
+}
+
+/// A change of the GRANDPA authorities that has been signalled, but not yet
+/// enacted
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+struct PendingChange {
+    /// The block that enacts the change.  It is finalized by the old
+    /// authorities, and its descendants by the new ones.
+    enacted_at: BlockNumber,
+    authorities: Vec<Authority>,
+}
+
+/// A finalized block that is trusted, and the GRANDPA authorities that
+/// finalize its descendants.  This is the content of the checkpoint file.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+struct Checkpoint {
+    number: BlockNumber,
+    hash: H256,
+    /// The ID of the authority set, which is part of every signed vote
+    set_id: u64,
+    authorities: Vec<Authority>,
+    pending: Option<PendingChange>,
+}
+
+impl Checkpoint {
+    fn load(path: &Path) -> Result<Self, Error> {
+        let file = fs::File::open(path).map_err(|e| {
+            format!(
+                "Cannot open the checkpoint {}: {}.  Create it with `ledgeracio checkpoint`.",
+                path.display(),
+                e
+            )
+        })?;
+        Ok(serde_json::from_reader(BufReader::new(file))?)
+    }
+
+    fn save(&self, path: &Path) -> Result<(), Error> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?
+        }
+        // Replace the old checkpoint atomically, so that it cannot be lost if
+        // we are interrupted.
+        let temporary = path.with_extension("tmp");
+        fs::write(&temporary, serde_json::to_string_pretty(self)?)?;
+        fs::rename(temporary, path)?;
+        Ok(())
+    }
+}
+
+/// The checkpoint file: `path` if given, or
+/// `ledgeracio/<network>-checkpoint.json` in the user’s configuration
+/// directory
+pub(crate) fn checkpoint_path(
+    path: Option<&Path>,
+    network: Ss58AddressFormat,
+) -> Result<PathBuf, Error> {
+    if let Some(path) = path {
+        return Ok(path.to_owned())
+    }
+    let name = match network {
+        Ss58AddressFormat::KusamaAccount => "kusama",
+        Ss58AddressFormat::PolkadotAccount => "polkadot",
+        _ => return Err("Please supply a checkpoint file with --checkpoint".into()),
+    };
+    let config = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME").ok_or_else(|| "HOME is not set".to_owned())?)
+            .join(".config"),
+    };
+    Ok(config
+        .join("ledgeracio")
+        .join(format!("{}-checkpoint.json", name)))
+}
+
+/// The storage key of the GRANDPA storage item `name`
+fn grandpa_key(name: &[u8]) -> StorageKey {
+    // The GRANDPA pallet keeps its storage under its old name.
+    StorageKey([twox_128(b"GrandpaFinality"), twox_128(name)].concat())
+}
+
+/// A change of the GRANDPA authorities, as signalled in a header digest
+#[derive(Encode, Decode)]
+struct ScheduledChange {
+    next_authorities: Vec<(ed25519::Public, u64)>,
+    /// The number of blocks after the signal at which the change is enacted
+    delay: BlockNumber,
+}
+
+/// The change of the GRANDPA authorities signalled by `header`, if any
+fn signalled_change(header: &Header) -> Result<Option<ScheduledChange>, Error> {
+    let mut change = None;
+    for log in header.digest.logs() {
+        if let DigestItem::Consensus(GRANDPA_ENGINE_ID, data) = log {
+            // The first byte is the variant of `sp_finality_grandpa::ConsensusLog`.
+            match data.split_first() {
+                Some((1, mut data)) => change = Some(ScheduledChange::decode(&mut data)?),
+                Some((2, _)) => {
+                    return Err(format!(
+                        "Block #{} forces a change of the GRANDPA authorities, which cannot be \
+                         verified.  Please create a new checkpoint after it.",
+                        header.number
+                    )
+                    .into())
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(change)
+}
+
+/// A vote for a block
+#[derive(Encode, Decode, Clone, Copy)]
+struct Precommit {
+    target_hash: H256,
+    target_number: BlockNumber,
+}
+
+#[derive(Encode, Decode)]
+struct SignedPrecommit {
+    precommit: Precommit,
+    signature: ed25519::Signature,
+    id: ed25519::Public,
+}
+
+#[derive(Encode, Decode)]
+struct Commit {
+    target_hash: H256,
+    target_number: BlockNumber,
+    precommits: Vec<SignedPrecommit>,
+}
+
+/// A GRANDPA justification, as encoded by `sc_finality_grandpa`
+#[derive(Encode, Decode)]
+struct Justification {
+    round: u64,
+    commit: Commit,
+    /// The headers between the finalized block and the blocks voted for
+    votes_ancestries: Vec<Header>,
+}
+
+/// The message signed by an authority voting for `precommit`
+fn precommit_message(precommit: Precommit, round: u64, set_id: u64) -> Vec<u8> {
+    // 1 is the variant of `finality_grandpa::Message::Precommit`.
+    (1_u8, precommit, round, set_id).encode()
+}
+
+/// Check that `justification` finalizes block `number` with hash `hash`.  It
+/// must be signed by the authority set `authorities` with ID `set_id`, with
+/// more than two thirds of their weight.
+fn verify_justification(
+    justification: &[u8],
+    hash: H256,
+    number: BlockNumber,
+    set_id: u64,
+    authorities: &[Authority],
+) -> Result<(), Error> {
+    let invalid = |reason: &str| -> Error {
+        format!("Invalid justification of block #{}: {}", number, reason).into()
+    };
+    let Justification {
+        round,
+        commit,
+        votes_ancestries,
+    } = Justification::decode(&mut &*justification)?;
+    if commit.target_hash != hash || commit.target_number != number {
+        return Err(invalid("it is for another block"))
+    }
+    let ancestry: HashMap<H256, Header> = votes_ancestries
+        .into_iter()
+        .map(|header| (header.hash(), header))
+        .collect();
+    let mut signers = HashSet::new();
+    let mut weight = 0_u64;
+    for SignedPrecommit {
+        precommit,
+        signature,
+        id,
+    } in commit.precommits
+    {
+        let authority = authorities
+            .iter()
+            .find(|authority| authority.id.0 == id.0)
+            .ok_or_else(|| invalid("it has a vote from a non-authority"))?;
+        if !ed25519::Pair::verify(&signature, precommit_message(precommit, round, set_id), &id) {
+            return Err(invalid("it has a bad signature"))
+        }
+        // A vote for a descendant is also a vote for the block.
+        let mut target = precommit.target_hash;
+        while target != hash {
+            target = ancestry
+                .get(&target)
+                .ok_or_else(|| invalid("it has a vote for a block that is not a descendant"))?
+                .parent_hash
+        }
+        if signers.insert(id.0) {
+            weight = weight.saturating_add(authority.weight)
+        }
+    }
+    let total = authorities.iter().fold(0_u64, |total, authority| {
+        total.saturating_add(authority.weight)
+    });
+    // GRANDPA tolerates less than a third of the weight being faulty.
+    if weight < total - total.saturating_sub(1) / 3 {
+        return Err(invalid("it has too few votes"))
+    }
+    Ok(())
+}
+
+/// Check the justification of the block of `checkpoint` against its
+/// authorities
+async fn check_justification(
+    rpc: &jsonrpsee::Client,
+    checkpoint: &Checkpoint,
+) -> Result<(), Error> {
+    let justification = rpc::justification(rpc, checkpoint.hash)
+        .await?
+        .ok_or_else(|| {
+            format!(
+                "The node has not kept the justification of block #{}, so it cannot be verified",
+                checkpoint.number
+            )
+        })?;
+    verify_justification(
+        &justification.0,
+        checkpoint.hash,
+        checkpoint.number,
+        checkpoint.set_id,
+        &checkpoint.authorities,
+    )
+}
+
+/// Checks storage values against proofs, at blocks that are known to be
+/// finalized
+pub(crate) struct Verifier {
+    rpc: jsonrpsee::Client,
+    /// The number of the first verified block
+    first: BlockNumber,
+    /// The hashes of the verified blocks, in order
+    hashes: Vec<H256>,
+    /// The state roots of the verified blocks
+    state_roots: HashMap<H256, H256>,
+    /// The number of the finalized head when the verifier was created
+    finalized: BlockNumber,
+}
+
+impl Verifier {
+    /// Follow the finalized chain from the checkpoint in `path` to the most
+    /// recent block whose justification the node at `host` keeps, and save
+    /// that block as the new checkpoint.  This is slow if the checkpoint is
+    /// old, as every header in between is fetched.
+    pub(crate) async fn new(host: &str, path: &Path) -> Result<Self, Error> {
+        let rpc = rpc::connect(host).await?;
+        let mut checkpoint = Checkpoint::load(path)?;
+        let header = rpc::header(&rpc, checkpoint.hash).await?;
+        if header.number != checkpoint.number {
+            return Err(format!(
+                "Block {:?} is #{}, not #{} as the checkpoint says",
+                checkpoint.hash, header.number, checkpoint.number
+            )
+            .into())
+        }
+        let finalized = rpc::header(&rpc, rpc::finalized_head(&rpc).await?)
+            .await?
+            .number;
+        let mut verifier = Self {
+            first: checkpoint.number,
+            hashes: vec![checkpoint.hash],
+            state_roots: iter::once((checkpoint.hash, header.state_root)).collect(),
+            finalized,
+            rpc,
+        };
+        let target = finalized - finalized % JUSTIFICATION_PERIOD;
+        if target <= checkpoint.number {
+            return Ok(verifier)
+        }
+        while checkpoint.number < target {
+            let numbers = checkpoint.number + 1..=target.min(checkpoint.number + HEADER_BATCH);
+            let hashes = try_join_all(
+                numbers.map(|number| rpc::block_hash(&verifier.rpc, BlockId::Number(number))),
+            )
+            .await?;
+            let headers =
+                try_join_all(hashes.iter().map(|&hash| rpc::header(&verifier.rpc, hash))).await?;
+            for (hash, header) in hashes.into_iter().zip(headers) {
+                if header.parent_hash != checkpoint.hash {
+                    return Err(format!(
+                        "Block #{} is not a child of block #{}, so the node is not following the \
+                         finalized chain",
+                        header.number, checkpoint.number
+                    )
+                    .into())
+                }
+                checkpoint.number = header.number;
+                checkpoint.hash = hash;
+                if let Some(change) = signalled_change(&header)? {
+                    if checkpoint.pending.is_some() {
+                        return Err(format!(
+                            "Block #{} signals a change of the GRANDPA authorities while another \
+                             is pending",
+                            header.number
+                        )
+                        .into())
+                    }
+                    checkpoint.pending = Some(PendingChange {
+                        enacted_at: header.number.saturating_add(change.delay),
+                        authorities: change
+                            .next_authorities
+                            .into_iter()
+                            .map(Authority::from)
+                            .collect(),
+                    })
+                }
+                let enacted = match checkpoint.pending.take() {
+                    Some(pending) if pending.enacted_at == header.number => Some(pending),
+                    pending => {
+                        checkpoint.pending = pending;
+                        None
+                    }
+                };
+                // This also proves that every block since the last
+                // justification is finalized, as they are its ancestors.
+                if enacted.is_some() || header.number == target {
+                    check_justification(&verifier.rpc, &checkpoint).await?
+                }
+                if let Some(PendingChange { authorities, .. }) = enacted {
+                    checkpoint.set_id += 1;
+                    checkpoint.authorities = authorities
+                }
+                verifier.hashes.push(hash);
+                verifier.state_roots.insert(hash, header.state_root);
+            }
+            info!("Verified headers up to #{}", checkpoint.number)
+        }
+        checkpoint.save(path)?;
+        Ok(verifier)
+    }
+
+    /// The most recent verified block
+    pub(crate) fn latest(&self) -> H256 {
+        *self
+            .hashes
+            .last()
+            .expect("the checkpoint is always verified")
+    }
+
+    /// The number of the most recent verified block, and how many blocks it
+    /// is behind the finalized head
+    pub(crate) fn lag(&self) -> (BlockNumber, BlockNumber) {
+        let latest = self.first
+            + BlockNumber::try_from(self.hashes.len() - 1).expect("block numbers fit in a u32");
+        (latest, self.finalized.saturating_sub(latest))
+    }
+
+    /// The hash of `block`, which must have been verified
+    pub(crate) fn block_hash(&self, block: BlockId) -> Result<H256, Error> {
+        let (hash, block) = match block {
+            BlockId::Number(number) => (
+                number
+                    .checked_sub(self.first)
+                    .and_then(|index| usize::try_from(index).ok())
+                    .and_then(|index| self.hashes.get(index))
+                    .copied(),
+                format!("#{}", number),
+            ),
+            BlockId::Hash(hash) => (
+                Some(hash).filter(|hash| self.state_roots.contains_key(hash)),
+                format!("{:?}", hash),
+            ),
+        };
+        hash.ok_or_else(|| {
+            format!(
+                "Block {} has not been verified.  Only blocks from the checkpoint (#{}) to the \
+                 last finalized block with a justification can be read with --verify-proofs.",
+                block, self.first
+            )
+            .into()
+        })
+    }
+
+    /// Fetch the values of `keys` at block `at`, checking them against a
+    /// storage proof.  The results are in the same order as `keys`.  Only the
+    /// values are checked, so the keys must not come from the node: see
+    /// [`crate::layout`].
+    pub(crate) async fn fetch(
+        &self,
+        keys: &[StorageKey],
+        at: H256,
+    ) -> Result<Vec<Option<StorageData>>, Error> {
+        let state_root = *self
+            .state_roots
+            .get(&at)
+            .ok_or_else(|| format!("Block {:?} has not been verified", at))?;
+        let proof = rpc::read_proof(&self.rpc, keys, at).await?;
+        let mut values = read_proof_check::<Blake2Hasher, _>(
+            state_root,
+            StorageProof::new(proof),
+            keys.iter().map(|key| &key.0),
+        )
+        .map_err(|e| format!("Invalid storage proof for block {:?}: {}", at, e))?;
+        Ok(keys
+            .iter()
+            .map(|key| values.remove(&key.0).flatten().map(StorageData))
+            .collect())
+    }
+}
+
+/// Save a checkpoint at block `hash` to `path`.  The block must be finalized,
+/// and its hash must come from a source the user trusts, as everything
+/// verified later depends on it.  Its GRANDPA authorities are read from its
+/// state, checked against a storage proof.
+pub(crate) async fn create_checkpoint(host: &str, hash: H256, path: &Path) -> Result<(), Error> {
+    let rpc = rpc::connect(host).await?;
+    let header = rpc::header(&rpc, hash).await?;
+    let verifier = Verifier {
+        first: header.number,
+        hashes: vec![hash],
+        state_roots: iter::once((hash, header.state_root)).collect(),
+        finalized: header.number,
+        rpc,
+    };
+    let keys = [
+        StorageKey(AUTHORITIES_KEY.to_vec()),
+        grandpa_key(b"CurrentSetId"),
+        grandpa_key(b"PendingChange"),
+    ];
+    let mut values = verifier.fetch(&keys, hash).await?.into_iter();
+    let (authorities, set_id, pending) = (
+        values.next().flatten(),
+        values.next().flatten(),
+        values.next().flatten(),
+    );
+    if pending.is_some() {
+        return Err(format!(
+            "A change of the GRANDPA authorities is pending at block #{}.  Please choose a block \
+             after it has been enacted.",
+            header.number
+        )
+        .into())
+    }
+    let authorities = authorities
+        .ok_or_else(|| format!("Block #{} has no GRANDPA authorities", header.number))?;
+    let (version, authorities) = <(u8, Vec<(ed25519::Public, u64)>)>::decode(&mut &*authorities.0)?;
+    if version != AUTHORITIES_VERSION {
+        return Err(format!("Unsupported GRANDPA authority list version {}", version).into())
+    }
+    let set_id = match set_id {
+        Some(StorageData(set_id)) => u64::decode(&mut &*set_id)?,
+        None => 0,
+    };
+    let checkpoint = Checkpoint {
+        number: header.number,
+        hash,
+        set_id,
+        authorities: authorities.into_iter().map(Authority::from).collect(),
+        pending: None,
+    };
+    checkpoint.save(path)?;
+    println!(
+        "Saved a checkpoint at block #{} with {} GRANDPA authorities to {}",
+        checkpoint.number,
+        checkpoint.authorities.len(),
+        path.display()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use substrate_subxt::sp_runtime::generic::Digest;
+
+    #[test]
+    fn verify_justification_works() {
+        let pairs: Vec<_> = (1..=4)
+            .map(|seed| ed25519::Pair::from_seed(&[seed; 32]))
+            .collect();
+        let authorities: Vec<_> = pairs
+            .iter()
+            .map(|pair| Authority::from((pair.public(), 1)))
+            .collect();
+        let parent = H256::repeat_byte(1);
+        let finalized = Header::new(10, H256::zero(), H256::zero(), parent, Digest::default());
+        let child = Header::new(
+            11,
+            H256::zero(),
+            H256::zero(),
+            finalized.hash(),
+            Digest::default(),
+        );
+        let (hash, round, set_id) = (finalized.hash(), 3, 7);
+        let justification = |signers: &[&ed25519::Pair], set_id, target: &Header| {
+            let precommit = Precommit {
+                target_hash: target.hash(),
+                target_number: target.number,
+            };
+            let precommits = signers
+                .iter()
+                .map(|pair| SignedPrecommit {
+                    precommit,
+                    signature: pair.sign(&precommit_message(precommit, round, set_id)),
+                    id: pair.public(),
+                })
+                .collect();
+            Justification {
+                round,
+                commit: Commit {
+                    target_hash: hash,
+                    target_number: 10,
+                    precommits,
+                },
+                votes_ancestries: vec![child.clone()],
+            }
+            .encode()
+        };
+        let verify = |justification: Vec<u8>| {
+            verify_justification(&justification, hash, 10, set_id, &authorities).is_ok()
+        };
+        let [a, b, c, d] = [&pairs[0], &pairs[1], &pairs[2], &pairs[3]];
+        assert!(verify(justification(&[a, b, c], set_id, &finalized)));
+        // A vote for a descendant counts.
+        assert!(verify(justification(&[a, b, d], set_id, &child)));
+        assert!(!verify(justification(&[a, b], set_id, &finalized)));
+        // An authority voting twice only counts once.
+        assert!(!verify(justification(&[a, a, b], set_id, &finalized)));
+        assert!(!verify(justification(
+            &[a, b, c, d],
+            set_id + 1,
+            &finalized
+        )));
+        let other = ed25519::Pair::from_seed(&[5; 32]);
+        assert!(!verify(justification(&[a, b, &other], set_id, &finalized)));
+        assert!(verify_justification(
+            &justification(&[a, b, c], set_id, &finalized),
+            child.hash(),
+            11,
+            set_id,
+            &authorities
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,463 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of ledgeracio.
+//
+// ledgeracio is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// ledgeracio is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ledgeracio.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Reward history commands
+
+use super::{AccountType, Error, StructOpt};
+use crate::{common::{active_era, block_or_best, fetch_chunked, fetch_many, fetch_or_default,
+                     fetch_validators, payee_code, AddressSource, At, FETCH_CHUNK},
+            output::OutputFormat,
+            payouts::{claimed_eras, rewarded_validators, split_reward, ErasPoints},
+            staking::{EraIndex, ErasStakersClippedStore, ErasValidatorPrefsStore,
+                      ErasValidatorRewardStore}};
+use core::{future::Future, pin::Pin};
+use futures::future::{join, join3, try_join_all};
+use ledgeracio::{pad, parse_address, validate_network};
+use serde::Serialize;
+use std::{collections::HashMap, convert::TryInto, marker::PhantomData, str::FromStr};
+use substrate_subxt::{sp_core::{crypto::{AccountId32 as AccountId, Ss58AddressFormat, Ss58Codec},
+                                H256},
+                      sp_runtime::{traits::Zero, Perbill},
+                      staking::{CurrentEraStore, HistoryDepthStore, LedgerStore, PayeeStore,
+                                RewardDestination},
+                      Client, KusamaRuntime};
+
+#[derive(StructOpt, Debug)]
+pub(crate) enum Rewards {
+    /// Export the per-era rewards of every account on the device, and of any
+    /// watch-only accounts
+    Export {
+        /// The first era to export.  Defaults to the oldest era in the history
+        /// window.
+        #[structopt(long)]
+        from_era: Option<EraIndex>,
+        /// The last era to export.  Defaults to the last era that has ended.
+        #[structopt(long)]
+        to_era: Option<EraIndex>,
+        /// `csv` or `json`.  `--output json` implies `json`.
+        #[structopt(long, default_value = "csv")]
+        format: ExportFormat,
+        /// The stash address of a watch-only account to include.  May be given
+        /// more than once.
+        #[structopt(long, parse(try_from_str = parse_address))]
+        watch: Vec<(AccountId, u8)>,
+        /// Do not include the accounts on the device
+        #[structopt(long)]
+        no_device: bool,
+    },
+}
+
+/// The format of exported rewards
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ExportFormat {
+    Csv,
+    Json,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &*s.to_ascii_lowercase() {
+            "csv" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("Invalid format {}: expected `csv` or `json`", s)),
+        }
+    }
+}
+
+/// The reward of a stash account from a single validator in a single era
+#[derive(Debug)]
+struct RewardRecord {
+    /// The stash account that was rewarded
+    account: AccountId,
+    /// The era
+    era: EraIndex,
+    /// The stash account of the validator that earned the reward.  This is
+    /// `account` itself for a validator’s own reward.
+    validator: AccountId,
+    /// The estimated reward, in planck, including any commission
+    reward: u128,
+    /// The commission earned, in planck.  Always zero for nominators.
+    commission: u128,
+    /// The reward destination of `account` at the block read, which may
+    /// differ from its destination in `era`
+    current_payee: RewardDestination<AccountId>,
+    /// Whether the validator has claimed the payout for the era
+    claimed: bool,
+}
+
+/// A [`RewardRecord`] in the form that is exported.  Amounts are given as
+/// strings, as they may not fit in a JSON number.
+#[derive(Serialize)]
+struct ExportedRecord {
+    account: String,
+    era: EraIndex,
+    validator: String,
+    reward_planck: String,
+    reward: String,
+    commission_planck: String,
+    commission: String,
+    current_payee: String,
+    claimed: bool,
+}
+
+impl ExportedRecord {
+    fn new(record: &RewardRecord, decimals: u8, network: Ss58AddressFormat) -> Self {
+        Self {
+            account: record.account.to_ss58check_with_version(network),
+            era: record.era,
+            validator: record.validator.to_ss58check_with_version(network),
+            reward_planck: record.reward.to_string(),
+            reward: pad(decimals, record.reward),
+            commission_planck: record.commission.to_string(),
+            commission: pad(decimals, record.commission),
+            current_payee: payee_code(&record.current_payee, network),
+            claimed: record.claimed,
+        }
+    }
+
+    fn to_csv(&self) -> String {
+        format!(
+            "{},{},{},{},{},{},{},{},{}",
+            self.account,
+            self.era,
+            self.validator,
+            self.reward_planck,
+            self.reward,
+            self.commission_planck,
+            self.commission,
+            self.current_payee,
+            self.claimed
+        )
+    }
+}
+
+/// The header line of exported CSV
+const CSV_HEADER: &str =
+    "account,era,validator,reward_planck,reward,commission_planck,commission,current_payee,claimed";
+
+/// The range of eras for which rewards are known: from the oldest era in the
+/// history window to the last era that has ended
+pub(crate) async fn era_bounds(
+    client: &Client<KusamaRuntime>,
+    at: At<'_>,
+) -> Result<(EraIndex, EraIndex), Error> {
+    let history_depth = fetch_or_default(
+        client,
+        &HistoryDepthStore {
+            _runtime: PhantomData,
+        },
+        at,
+    );
+    let current_era = fetch_or_default(
+        client,
+        &CurrentEraStore {
+            _runtime: PhantomData,
+        },
+        at,
+    );
+    let (history_depth, current_era, active_era) =
+        join3(history_depth, current_era, active_era(client, at)).await;
+    let last_ended = active_era?
+        .checked_sub(1)
+        .ok_or_else(|| "No era has ended yet".to_owned())?;
+    Ok((current_era?.saturating_sub(history_depth?), last_ended))
+}
+
+/// The stash accounts of the device accounts of type `account_type`
+async fn device_stashes(
+    client: &Client<KusamaRuntime>,
+    keystore: &super::HardStore,
+    network: Ss58AddressFormat,
+    account_type: AccountType,
+    at: At<'_>,
+) -> Result<Vec<AccountId>, Error> {
+    let controllers = fetch_validators(
+        client,
+        AddressSource::Device(None, keystore),
+        network,
+        account_type,
+        at,
+    )
+    .await?;
+    let ledgers: Vec<_> = controllers
+        .into_iter()
+        .map(|controller| LedgerStore { controller })
+        .collect();
+    Ok(fetch_many(client, &ledgers, at)
+        .await?
+        .into_iter()
+        .flatten()
+        .map(|ledger| ledger.stash)
+        .collect())
+}
+
+/// The stake of an exported account in the clipped exposure of a validator
+struct Stake {
+    era: EraIndex,
+    validator: AccountId,
+    /// The index of the account in the exported accounts
+    account: usize,
+    value: u128,
+    /// The total stake in the clipped exposure
+    total: u128,
+}
+
+/// Find the stakes of `stashes` in `eras`, in their own validators (if any)
+/// and in every validator they backed.  The clipped exposure of every
+/// validator that earned points is read, a chunk at a time, so this is slow
+/// for many eras.  The reward points of each era are returned as well.
+async fn stakes(
+    client: &Client<KusamaRuntime>,
+    stashes: &[AccountId],
+    eras: &[EraIndex],
+    at: At<'_>,
+) -> Result<(Vec<Stake>, ErasPoints), Error> {
+    let (rewarded, era_points) = rewarded_validators(client, eras, at).await?;
+    let exposures: Vec<_> = rewarded
+        .into_iter()
+        .map(|(era, stash)| ErasStakersClippedStore { era, stash })
+        .collect();
+    let mut stakes = vec![];
+    // Only the stakes of `stashes` are kept, as the exposures themselves
+    // are large.
+    for chunk in exposures.chunks(FETCH_CHUNK) {
+        for (store, exposure) in chunk.iter().zip(fetch_many(client, chunk, at).await?) {
+            let exposure = match exposure {
+                Some(exposure) if exposure.total != 0 => exposure,
+                _ => continue,
+            };
+            for (account, stash) in stashes.iter().enumerate() {
+                let value = if *stash == store.stash {
+                    exposure.own
+                } else {
+                    match exposure.others.iter().find(|i| i.who == *stash) {
+                        Some(individual) => individual.value,
+                        None => continue,
+                    }
+                };
+                stakes.push(Stake {
+                    era: store.era,
+                    validator: store.stash.clone(),
+                    account,
+                    value,
+                    total: exposure.total,
+                })
+            }
+        }
+    }
+    stakes.sort_by_key(|stake| stake.account);
+    Ok((stakes, era_points))
+}
+
+/// Find the rewards of the stash accounts `stashes` in `eras`, from their own
+/// validators (if any) and from every validator they backed.  The records of
+/// each account are together, in the same order as `stashes`.
+async fn rewards(
+    client: &Client<KusamaRuntime>,
+    stashes: &[AccountId],
+    eras: &[EraIndex],
+    at: At<'_>,
+) -> Result<Vec<RewardRecord>, Error> {
+    let at = At {
+        hash: Some(block_or_best(client, at.hash).await?),
+        ..at
+    };
+    let payees = stashes.iter().map(|stash| {
+        fetch_or_default(
+            client,
+            &PayeeStore {
+                stash: stash.clone(),
+            },
+            at,
+        )
+    });
+    let (payees, stakes) = join(try_join_all(payees), stakes(client, stashes, eras, at)).await;
+    let (payees, (stakes, era_points)) = (payees?, stakes?);
+    let mut validators: Vec<_> = stakes.iter().map(|stake| stake.validator.clone()).collect();
+    validators.sort_unstable();
+    validators.dedup();
+    let mut backed_eras: Vec<EraIndex> = stakes.iter().map(|stake| stake.era).collect();
+    backed_eras.sort_unstable();
+    backed_eras.dedup();
+    let era_rewards: Vec<_> = backed_eras
+        .iter()
+        .map(|&era| ErasValidatorRewardStore {
+            era,
+            _runtime: PhantomData,
+        })
+        .collect();
+    let prefs: Vec<_> = stakes
+        .iter()
+        .map(|stake| ErasValidatorPrefsStore {
+            era: stake.era,
+            stash: stake.validator.clone(),
+        })
+        .collect();
+    let (claimed, era_rewards, prefs) = join3(
+        claimed_eras(client, &validators, at),
+        fetch_many(client, &era_rewards, at),
+        fetch_chunked(client, &prefs, at),
+    )
+    .await;
+    let (claimed, prefs) = (claimed?, prefs?);
+    let era_rewards: HashMap<_, _> = backed_eras.into_iter().zip(era_rewards?).collect();
+    let mut records = vec![];
+    for (stake, prefs) in stakes.into_iter().zip(prefs) {
+        let Stake {
+            era,
+            validator,
+            account,
+            value,
+            total,
+        } = stake;
+        let (points, total_points) = era_points.get(&era).map_or((0, 0), |points| {
+            let individual = points
+                .individual
+                .get(&validator)
+                .cloned()
+                .unwrap_or_else(Zero::zero);
+            (individual, points.total)
+        });
+        let (commission, leftover) = split_reward(
+            era_rewards[&era].unwrap_or_default(),
+            points,
+            total_points,
+            prefs.map_or(Perbill::zero(), |prefs| prefs.commission),
+        );
+        let stash = &stashes[account];
+        let commission = if validator == *stash { commission } else { 0 };
+        let reward = commission
+            .saturating_add(Perbill::from_rational_approximation(value, total) * leftover);
+        let claimed = claimed
+            .get(&validator)
+            .map_or(false, |claimed| claimed.binary_search(&era).is_ok());
+        records.push(RewardRecord {
+            account: stash.clone(),
+            era,
+            validator,
+            reward,
+            commission,
+            current_payee: payees[account].clone(),
+            claimed,
+        })
+    }
+    Ok(records)
+}
+
+pub(crate) async fn main<T: FnOnce() -> Result<super::HardStore, Error>>(
+    cmd: Rewards,
+    client: Pin<Box<dyn Future<Output = Result<Client<KusamaRuntime>, Error>>>>,
+    network: Ss58AddressFormat,
+    keystore: T,
+    output: OutputFormat,
+    at: At<'_>,
+) -> Result<Option<H256>, Error> {
+    match cmd {
+        Rewards::Export {
+            from_era,
+            to_era,
+            format,
+            watch,
+            no_device,
+        } => {
+            let mut stashes = vec![];
+            for (stash, provided_network) in watch {
+                validate_network(
+                    &stash.to_ss58check_with_version(provided_network.try_into().unwrap()),
+                    provided_network,
+                    network,
+                )?;
+                stashes.push(stash)
+            }
+            let client = client.await?;
+            if !no_device {
+                let keystore = keystore()?;
+                for &account_type in &[AccountType::Validator, AccountType::Nominator] {
+                    stashes.extend(
+                        device_stashes(&client, &keystore, network, account_type, at).await?,
+                    )
+                }
+            }
+            if stashes.is_empty() {
+                return Err("No accounts to export rewards for".to_owned().into())
+            }
+            let (oldest, newest) = era_bounds(&client, at).await?;
+            let (from_era, to_era) = (from_era.unwrap_or(oldest), to_era.unwrap_or(newest));
+            if from_era < oldest || to_era > newest {
+                return Err(format!(
+                    "Rewards are only known for eras {} to {}, but eras {} to {} were requested",
+                    oldest, newest, from_era, to_era
+                )
+                .into())
+            }
+            if from_era > to_era {
+                return Err(format!(
+                    "The first era ({}) is after the last era ({})",
+                    from_era, to_era
+                )
+                .into())
+            }
+            stashes.sort_unstable();
+            stashes.dedup();
+            let eras: Vec<_> = (from_era..=to_era).collect();
+            let decimals = client.properties().token_decimals;
+            let records = rewards(&client, &stashes, &eras, at)
+                .await?
+                .into_iter()
+                .map(|record| ExportedRecord::new(&record, decimals, network));
+            match (format, output) {
+                (ExportFormat::Csv, OutputFormat::Text) => {
+                    println!("{}", CSV_HEADER);
+                    for record in records {
+                        println!("{}", record.to_csv())
+                    }
+                }
+                (ExportFormat::Json, _) | (_, OutputFormat::Json) => {
+                    let records: Vec<_> = records.collect();
+                    println!("{}", serde_json::to_string_pretty(&records)?)
+                }
+            }
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_matches_header() {
+        let record = ExportedRecord::new(
+            &RewardRecord {
+                account: AccountId::default(),
+                era: 5,
+                validator: AccountId::default(),
+                reward: 1_500_000_000_000,
+                commission: 0,
+                current_payee: RewardDestination::Staked,
+                claimed: true,
+            },
+            12,
+            Ss58AddressFormat::KusamaAccount,
+        );
+        let csv = record.to_csv();
+        assert_eq!(csv.split(',').count(), CSV_HEADER.split(',').count());
+        assert!(csv.contains(",1500000000000,1.5,0,0,staked,true"));
+    }
+}
@@ -0,0 +1,222 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of ledgeracio.
+//
+// ledgeracio is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// ledgeracio is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ledgeracio.  If not, see <http://www.gnu.org/licenses/>.
+
+//! RPC calls that `substrate_subxt::Client` does not expose
+
+use super::Error;
+use jsonrpsee::{common::Params, Client};
+use serde::Deserialize;
+use serde_json::to_value;
+use std::str::FromStr;
+use substrate_subxt::{sp_core::{storage::{StorageChangeSet, StorageData, StorageKey},
+                                Bytes, H256},
+                      sp_runtime::{generic,
+                                   traits::{BlakeTwo256, Header as _}}};
+
+/// A block header of Polkadot or Kusama
+pub(crate) type Header = generic::Header<u32, BlakeTwo256>;
+
+/// The number of keys to request at once
+const PAGE_SIZE: usize = 1000;
+
+/// A block, given by number or by hash
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BlockId {
+    Number(u32),
+    Hash(H256),
+}
+
+impl FromStr for BlockId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with("0x") {
+            let bytes =
+                hex::decode(&s[2..]).map_err(|e| format!("Invalid block hash {}: {}", s, e))?;
+            if bytes.len() == 32 {
+                Ok(Self::Hash(H256::from_slice(&bytes)))
+            } else {
+                Err(format!("Invalid block hash {}: expected 32 bytes", s))
+            }
+        } else {
+            s.parse().map(Self::Number).map_err(|_| {
+                format!(
+                    "Invalid block {}: expected a number or a 0x-prefixed hash",
+                    s
+                )
+            })
+        }
+    }
+}
+
+/// Connect to the node at `url`, in the same way as
+/// [`substrate_subxt::ClientBuilder`] does
+pub(crate) async fn connect(url: &str) -> Result<Client, Error> {
+    if url.starts_with("ws://") || url.starts_with("wss://") {
+        Ok(jsonrpsee::ws_client(url).await?)
+    } else {
+        Ok(jsonrpsee::http_client(url))
+    }
+}
+
+/// Fetch all storage keys that start with `prefix` at block `at`
+pub(crate) async fn storage_keys(
+    client: &Client,
+    prefix: &StorageKey,
+    at: H256,
+) -> Result<Vec<StorageKey>, Error> {
+    let mut keys: Vec<StorageKey> = vec![];
+    loop {
+        let params = Params::Array(vec![
+            to_value(prefix)?,
+            to_value(PAGE_SIZE)?,
+            to_value(keys.last())?,
+            to_value(at)?,
+        ]);
+        let page: Vec<StorageKey> = client.request("state_getKeysPaged", params).await?;
+        let done = page.len() < PAGE_SIZE;
+        keys.extend(page);
+        if done {
+            return Ok(keys)
+        }
+    }
+}
+
+/// Generate new session keys on the node, returning their public parts.  The
+/// node must allow unsafe RPC calls.
+pub(crate) async fn rotate_keys(client: &Client) -> Result<Bytes, Error> {
+    Ok(client.request("author_rotateKeys", Params::None).await?)
+}
+
+/// Check that the node has the secret parts of all of `keys`, as returned by
+/// [`rotate_keys`]
+pub(crate) async fn has_session_keys(client: &Client, keys: Bytes) -> Result<bool, Error> {
+    let params = Params::Array(vec![to_value(keys)?]);
+    Ok(client.request("author_hasSessionKeys", params).await?)
+}
+
+/// Check that the node has the secret part of the public key `public` of
+/// type `key_type`
+pub(crate) async fn has_key(client: &Client, public: Bytes, key_type: &str) -> Result<bool, Error> {
+    let params = Params::Array(vec![to_value(public)?, to_value(key_type)?]);
+    Ok(client.request("author_hasKey", params).await?)
+}
+
+/// Fetch the SCALE-encoded runtime metadata at block `at`, or at the best
+/// block if `at` is `None`
+pub(crate) async fn raw_metadata(client: &Client, at: Option<H256>) -> Result<Vec<u8>, Error> {
+    let params = Params::Array(vec![to_value(at)?]);
+    let Bytes(bytes) = client.request("state_getMetadata", params).await?;
+    Ok(bytes)
+}
+
+/// Find the hash of the block `block` on the best chain.  A number is looked
+/// up, while a hash is checked to be known to the node.
+pub(crate) async fn block_hash(client: &Client, block: BlockId) -> Result<H256, Error> {
+    match block {
+        BlockId::Number(number) => {
+            let params = Params::Array(vec![to_value(number)?]);
+            let hash: Option<H256> = client.request("chain_getBlockHash", params).await?;
+            hash.ok_or_else(|| format!("Block #{} not found", number).into())
+        }
+        BlockId::Hash(hash) => {
+            let params = Params::Array(vec![to_value(hash)?]);
+            let header: Option<serde_json::Value> =
+                client.request("chain_getHeader", params).await?;
+            match header {
+                Some(_) => Ok(hash),
+                None => Err(format!("Block {:?} not found", hash).into()),
+            }
+        }
+    }
+}
+
+/// Fetch the hash of the last finalized block
+pub(crate) async fn finalized_head(client: &Client) -> Result<H256, Error> {
+    Ok(client
+        .request("chain_getFinalizedHead", Params::None)
+        .await?)
+}
+
+/// Fetch the header of block `hash`, checking that it has that hash
+pub(crate) async fn header(client: &Client, hash: H256) -> Result<Header, Error> {
+    let params = Params::Array(vec![to_value(hash)?]);
+    let header: Option<Header> = client.request("chain_getHeader", params).await?;
+    let header = header.ok_or_else(|| format!("Block {:?} not found", hash))?;
+    if header.hash() == hash {
+        Ok(header)
+    } else {
+        Err(format!("The node returned the wrong header for block {:?}", hash).into())
+    }
+}
+
+/// Fetch the SCALE-encoded GRANDPA justification of block `hash`, if the node
+/// has kept it
+pub(crate) async fn justification(client: &Client, hash: H256) -> Result<Option<Bytes>, Error> {
+    let params = Params::Array(vec![to_value(hash)?]);
+    let block: Option<serde_json::Value> = client.request("chain_getBlock", params).await?;
+    let mut block = block.ok_or_else(|| format!("Block {:?} not found", hash))?;
+    Ok(serde_json::from_value(block["justification"].take())?)
+}
+
+/// A proof of storage values, as returned by `state_getReadProof`
+#[derive(Deserialize)]
+struct ReadProof {
+    /// The trie nodes of the proof
+    proof: Vec<Bytes>,
+}
+
+/// Fetch a proof of the values of `keys` at block `at`
+pub(crate) async fn read_proof(
+    client: &Client,
+    keys: &[StorageKey],
+    at: H256,
+) -> Result<Vec<Vec<u8>>, Error> {
+    let params = Params::Array(vec![to_value(keys)?, to_value(at)?]);
+    let ReadProof { proof } = client.request("state_getReadProof", params).await?;
+    Ok(proof.into_iter().map(|Bytes(node)| node).collect())
+}
+
+/// Fetch the values of `keys` at block `at` with `state_queryStorageAt`.  Keys
+/// with no value are either omitted or returned as `None`.
+pub(crate) async fn query_storage_at(
+    client: &Client,
+    keys: &[StorageKey],
+    at: H256,
+) -> Result<Vec<(StorageKey, Option<StorageData>)>, Error> {
+    let params = Params::Array(vec![to_value(keys)?, to_value(at)?]);
+    let change_sets: Vec<StorageChangeSet<H256>> =
+        client.request("state_queryStorageAt", params).await?;
+    Ok(change_sets
+        .into_iter()
+        .flat_map(|change_set| change_set.changes)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_block_id_works() {
+        assert_eq!("1234".parse(), Ok(BlockId::Number(1234)));
+        let hash = format!("0x{}", "ab".repeat(32));
+        assert_eq!(hash.parse(), Ok(BlockId::Hash(H256::repeat_byte(0xab))));
+        assert!("0xabcd".parse::<BlockId>().is_err());
+        assert!("-1".parse::<BlockId>().is_err());
+        assert!("latest".parse::<BlockId>().is_err());
+    }
+}
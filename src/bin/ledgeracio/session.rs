@@ -0,0 +1,59 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of ledgeracio.
+//
+// ledgeracio is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// ledgeracio is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ledgeracio.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Session storage items that `substrate_subxt::session` does not provide
+
+use codec::{Decode, Encode};
+use std::marker::PhantomData;
+use substrate_subxt::{session::Session, Store};
+
+const MODULE: &str = "Session";
+
+/// Counter for the number of sessions that have passed
+pub type SessionIndex = u32;
+
+/// The index of the current session
+#[derive(Clone, Encode, Decode, Debug, Store)]
+pub struct CurrentIndexStore<T: Session> {
+    #[store(returns = SessionIndex)]
+    /// Runtime marker
+    pub _runtime: PhantomData<T>,
+}
+
+/// The validators of the current session
+#[derive(Clone, Encode, Decode, Debug, Store)]
+pub struct ValidatorsStore<T: Session> {
+    #[store(returns = Vec<T::ValidatorId>)]
+    /// Runtime marker
+    pub _runtime: PhantomData<T>,
+}
+
+/// The session keys that a validator will use from the session after the
+/// next one, keyed by validator
+#[derive(Clone, Encode, Decode, Debug, Store)]
+pub struct NextKeysStore<T: Session> {
+    #[store(returns = T::Keys)]
+    /// The validator
+    pub validator_id: T::ValidatorId,
+}
+
+/// The validators of the next session, along with their session keys
+#[derive(Clone, Encode, Decode, Debug, Store)]
+pub struct QueuedKeysStore<T: Session> {
+    #[store(returns = Vec<(T::ValidatorId, T::Keys)>)]
+    /// Runtime marker
+    pub _runtime: PhantomData<T>,
+}
@@ -0,0 +1,205 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of ledgeracio.
+//
+// ledgeracio is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// ledgeracio is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ledgeracio.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Staking storage items and calls that `substrate_subxt::staking` does not
+//! provide, or provides in a form that does not match the current runtime.
+
+use codec::{Decode, Encode, HasCompact};
+use std::marker::PhantomData;
+use substrate_subxt::{sp_runtime::Perbill, staking::Staking, Call, Store};
+
+const MODULE: &str = "Staking";
+
+/// Counter for the number of eras that have passed
+pub type EraIndex = u32;
+
+/// Validator preferences, including the `blocked` flag that
+/// `substrate_subxt::staking::ValidatorPrefs` lacks
+#[derive(PartialEq, Eq, Clone, Copy, Encode, Decode, Debug)]
+pub struct ValidatorPrefs {
+    /// Reward that validator takes up-front; only the rest is split between
+    /// themselves and nominators.
+    #[codec(compact)]
+    pub commission: Perbill,
+    /// Whether or not this validator is accepting more nominations.
+    pub blocked: bool,
+}
+
+/// The exposure of a nominator to a validator
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Encode, Decode, Debug, Default)]
+pub struct IndividualExposure<AccountId, Balance: HasCompact> {
+    /// The stash account of the nominator
+    pub who: AccountId,
+    /// The amount exposed
+    #[codec(compact)]
+    pub value: Balance,
+}
+
+/// The amount of stake backing a validator in a given era
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Encode, Decode, Debug, Default)]
+pub struct Exposure<AccountId, Balance: HasCompact> {
+    /// The total balance backing this validator
+    #[codec(compact)]
+    pub total: Balance,
+    /// The validator’s own stash balance that is exposed
+    #[codec(compact)]
+    pub own: Balance,
+    /// The portions of nominators’ stashes that are exposed
+    pub others: Vec<IndividualExposure<AccountId, Balance>>,
+}
+
+/// A slash that has been reported, but not yet applied
+#[derive(PartialEq, Eq, Clone, Encode, Decode, Debug)]
+pub struct UnappliedSlash<AccountId, Balance> {
+    /// The stash account of the slashed validator
+    pub validator: AccountId,
+    /// The validator’s own slash
+    pub own: Balance,
+    /// The slashes of the nominators of the validator
+    pub others: Vec<(AccountId, Balance)>,
+    /// The reporters of the offence
+    pub reporters: Vec<AccountId>,
+    /// The amount paid to the reporters
+    pub payout: Balance,
+}
+
+/// The slashing spans of a stash.  A new span starts whenever the stash is
+/// slashed as a validator.
+#[derive(PartialEq, Eq, Clone, Encode, Decode, Debug)]
+pub struct SlashingSpans {
+    /// The index of the current span
+    pub span_index: u32,
+    /// The era in which the current span started
+    pub last_start: EraIndex,
+    /// The last era in which a nonzero slash happened
+    pub last_nonzero_slash: EraIndex,
+    /// The start eras of the previous spans, newest first
+    pub prior: Vec<EraIndex>,
+}
+
+/// The preferences of a validator, keyed by stash account
+#[derive(Clone, Encode, Decode, Debug, Store)]
+pub struct ValidatorsStore<T: Staking> {
+    #[store(returns = ValidatorPrefs)]
+    /// The stash account of the validator
+    pub stash: T::AccountId,
+}
+
+/// The slashing spans of a stash, if it has ever been slashed as a validator
+#[derive(Clone, Encode, Decode, Debug, Store)]
+pub struct SlashingSpansStore<T: Staking> {
+    #[store(returns = SlashingSpans)]
+    /// The stash account
+    pub stash: T::AccountId,
+}
+
+/// The exposure of a validator in a given era, keyed by era and stash
+/// account.  Empty if the validator was not in the active set in that era.
+#[derive(Clone, Encode, Decode, Debug, Store)]
+pub struct ErasStakersStore<T: Staking> {
+    #[store(returns = Exposure<T::AccountId, T::Balance>)]
+    /// The era
+    pub era: EraIndex,
+    /// The stash account of the validator
+    pub stash: T::AccountId,
+}
+
+/// The clipped exposure of a validator in a given era, keyed by era and stash
+/// account.  Only the nominators with the largest stakes are included, and
+/// only they are paid.
+#[derive(Clone, Encode, Decode, Debug, Store)]
+pub struct ErasStakersClippedStore<T: Staking> {
+    #[store(returns = Exposure<T::AccountId, T::Balance>)]
+    /// The era
+    pub era: EraIndex,
+    /// The stash account of the validator
+    pub stash: T::AccountId,
+}
+
+/// The total reward for all validators in a given era.  Only present once the
+/// era has ended.
+#[derive(Clone, Encode, Decode, Debug, Store)]
+pub struct ErasValidatorRewardStore<T: Staking> {
+    #[store(returns = T::Balance)]
+    /// The era
+    pub era: EraIndex,
+    /// Runtime marker
+    pub _runtime: PhantomData<T>,
+}
+
+/// The preferences of a validator in a given era, keyed by era and stash
+/// account
+#[derive(Clone, Encode, Decode, Debug, Store)]
+pub struct ErasValidatorPrefsStore<T: Staking> {
+    #[store(returns = ValidatorPrefs)]
+    /// The era
+    pub era: EraIndex,
+    /// The stash account of the validator
+    pub stash: T::AccountId,
+}
+
+/// The index of the session at which a given era started
+#[derive(Clone, Encode, Decode, Debug, Store)]
+pub struct ErasStartSessionIndexStore<T: Staking> {
+    #[store(returns = u32)]
+    /// The era
+    pub era: EraIndex,
+    /// Runtime marker
+    pub _runtime: PhantomData<T>,
+}
+
+/// The slashes that will be applied at the start of a given era
+#[derive(Clone, Encode, Decode, Debug, Store)]
+pub struct UnappliedSlashesStore<T: Staking> {
+    #[store(returns = Vec<UnappliedSlash<T::AccountId, T::Balance>>)]
+    /// The era
+    pub era: EraIndex,
+    /// Runtime marker
+    pub _runtime: PhantomData<T>,
+}
+
+/// The minimum commission a validator may set
+#[derive(Clone, Encode, Decode, Debug, Store)]
+pub struct MinCommissionStore<T: Staking> {
+    #[store(returns = Perbill)]
+    /// Runtime marker
+    pub _runtime: PhantomData<T>,
+}
+
+/// Declare the desire to validate with the given preferences
+#[derive(Clone, Debug, PartialEq, Call, Encode)]
+pub struct ValidateCall<T: Staking> {
+    /// Runtime marker
+    pub _runtime: PhantomData<T>,
+    /// The validator preferences
+    pub prefs: ValidatorPrefs,
+}
+
+/// Remove the given nominations from the calling validator
+#[derive(Clone, Debug, PartialEq, Call, Encode)]
+pub struct KickCall<T: Staking> {
+    /// The stash accounts of the nominators to remove
+    pub who: Vec<T::Address>,
+}
+
+/// Pay out the rewards of a validator and its nominators for an era
+#[derive(Clone, Debug, PartialEq, Call, Encode)]
+pub struct PayoutStakersCall<T: Staking> {
+    /// The stash account of the validator
+    pub validator_stash: T::AccountId,
+    /// The era to pay out
+    pub era: EraIndex,
+}
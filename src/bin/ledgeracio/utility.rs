@@ -0,0 +1,32 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of ledgeracio.
+//
+// ledgeracio is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// ledgeracio is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ledgeracio.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Calls of the utility pallet, which `substrate_subxt` does not provide
+
+use codec::Encode;
+use std::marker::PhantomData;
+use substrate_subxt::{system::System, Call, Encoded};
+
+const MODULE: &str = "Utility";
+
+/// Dispatch a batch of calls.  The batch stops at the first call that fails.
+#[derive(Clone, Debug, Call, Encode)]
+pub struct BatchCall<T: System> {
+    /// Runtime marker
+    pub _runtime: PhantomData<T>,
+    /// The calls to dispatch, as encoded by [`substrate_subxt::Client::encode`]
+    pub calls: Vec<Encoded>,
+}
@@ -18,18 +18,35 @@
 //! e.g. rotating session keys, set payment target, announcing
 //! intention to validate etc. Requires a network connection.
 
-use super::{common::parse_ppb, parse_reward_destination, AccountType, AddressSource, Error,
-            LedgeracioPath, StructOpt};
-use codec::Decode;
+use super::{common::{active_era, all_nominations, fetch, fetch_many, fetch_or_default,
+                     format_commission, format_reward_destination, parse_ppb, At},
+            parse_reward_destination, validate_reward_destination, AccountType, AddressSource,
+            Error, LedgeracioPath, StructOpt};
+use crate::{era::{display_era, era_progress, EraJson},
+            keys::{format_key, format_session_keys, session_keys},
+            output::{print_json, OutputFormat},
+            session::{NextKeysStore, QueuedKeysStore},
+            staking::{ErasStakersClippedStore, ErasStakersStore, ErasValidatorPrefsStore,
+                      IndividualExposure, KickCallExt, MinCommissionStore, ValidateCallExt,
+                      ValidatorPrefs, ValidatorsStore}};
+use codec::{Decode, Encode};
 use core::{future::Future, pin::Pin};
-use ledgeracio::parse_address;
+use futures::future::{join, join3, try_join_all};
+use ledgeracio::{parse_address, validate_network, Balance};
+use std::{convert::{TryFrom, TryInto},
+          fmt::Write,
+          marker::PhantomData};
 use substrate_subxt::{session::SetKeysCallExt,
-                      sp_core::{crypto::{AccountId32 as AccountId, Ss58AddressFormat},
-                                H256},
-                      sp_runtime::Perbill,
-                      staking::{BondedStore, ChillCallExt, RewardDestination, SetPayeeCallExt,
-                                ValidateCallExt, ValidatorPrefs},
-                      Client, KusamaRuntime, SessionKeys};
+                      sp_core::{crypto::{AccountId32 as AccountId, Ss58AddressFormat, Ss58Codec},
+                                Bytes, H256},
+                      sp_runtime::{traits::Zero, Perbill},
+                      staking::{BondedStore, ChillCallExt, LedgerStore, NominatorsStore,
+                                RewardDestination, SetPayeeCallExt, StakingLedger},
+                      Client, KusamaRuntime, SessionKeys, Signer, Store};
+
+/// Increases in commission at least this large (in parts per billion) cause a
+/// warning
+const COMMISSION_JUMP_WARNING: u32 = 50_000_000;
 
 #[derive(StructOpt, Debug)]
 pub(crate) enum Validator {
@@ -41,32 +58,160 @@ pub(crate) enum Validator {
     },
     /// Show status of the given Validator Controller key, or all if none is
     /// specified.
-    Show { index: Option<u32> },
+    Show {
+        index: Option<u32>,
+        /// Also list the nominators backing the validator in the active era,
+        /// and those currently nominating it.  This reads every nomination on
+        /// the chain, so it is slow.
+        #[structopt(long)]
+        nominators: bool,
+        /// Keep running, showing the status again whenever it changes on
+        /// chain, along with staking events concerning the validators
+        #[structopt(long)]
+        watch: bool,
+    },
     /// Announce intention to validate
     Announce {
         index: u32,
         #[structopt(parse(try_from_str = parse_ppb))]
         commission: Option<u32>,
+        /// Block new nominations.  Existing nominations are kept.
+        #[structopt(long, conflicts_with = "unblocked")]
+        blocked: bool,
+        /// Accept new nominations
+        #[structopt(long)]
+        unblocked: bool,
+    },
+    /// Remove nominations of this validator by the given nominator stash
+    /// accounts
+    Kick {
+        index: u32,
+        #[structopt(parse(try_from_str = parse_address), required = true)]
+        nominators: Vec<(AccountId, u8)>,
     },
     /// Chill (announce intention to cease validation)
     Chill { index: u32 },
+    /// Claim unclaimed payouts for the given validator, or all if none is
+    /// specified.
+    Payout {
+        /// The index of the validator controller key
+        #[structopt(long)]
+        index: Option<u32>,
+        /// The eras to claim.  Defaults to all eras with unclaimed payouts.
+        #[structopt(long)]
+        era: Vec<u32>,
+    },
     /// Replace a session key
     ReplaceKey {
         index: u32,
         #[structopt(parse(try_from_str = parse_keys))]
         keys: SessionKeys,
     },
+    /// Generate new session keys on the validator’s own node, and set them as
+    /// the session keys of the validator
+    RotateKeys {
+        index: u32,
+        /// The RPC endpoint of the validator’s node, such as
+        /// `ws://127.0.0.1:9944`.  It must allow unsafe RPC calls.
+        #[structopt(long)]
+        node: String,
+    },
+    /// Check that the session keys set on chain for the validator match those
+    /// on its node
+    CheckKeys {
+        index: u32,
+        /// The RPC endpoint of the validator’s node, such as
+        /// `ws://127.0.0.1:9944`.  It must allow unsafe RPC calls.
+        #[structopt(long)]
+        node: String,
+    },
     /// Set payment target
     #[structopt(name = "set-payee")]
     SetPayee {
         index: u32,
+        /// `staked`, `stash`, `controller`, `none`, or `account:<address>`
         #[structopt(parse(try_from_str = parse_reward_destination))]
-        target: RewardDestination<AccountId>,
+        target: RewardDestination<(AccountId, u8)>,
     },
     /// Display the address of the given index
     Address { index: u32 },
 }
 
+impl Validator {
+    /// Whether the command supports `--output json`
+    pub(crate) fn supports_json(&self) -> bool {
+        match self {
+            Self::ShowAddress { .. } | Self::Address { .. } => true,
+            Self::Show {
+                nominators, watch, ..
+            } => !nominators && !watch,
+            Self::Announce { .. }
+            | Self::Kick { .. }
+            | Self::Chill { .. }
+            | Self::Payout { .. }
+            | Self::ReplaceKey { .. }
+            | Self::RotateKeys { .. }
+            | Self::CheckKeys { .. }
+            | Self::SetPayee { .. } => false,
+        }
+    }
+
+    /// Whether the command signs and submits a transaction
+    pub(crate) fn submits(&self) -> bool {
+        match self {
+            Self::Announce { .. }
+            | Self::Kick { .. }
+            | Self::Chill { .. }
+            | Self::Payout { .. }
+            | Self::ReplaceKey { .. }
+            | Self::RotateKeys { .. }
+            | Self::SetPayee { .. } => true,
+            Self::Show { .. }
+            | Self::ShowAddress { .. }
+            | Self::CheckKeys { .. }
+            | Self::Address { .. } => false,
+        }
+    }
+
+    /// Whether the command supports `--verify-proofs`.  The nominators of a
+    /// validator are found by listing every nominator, which cannot be
+    /// verified.
+    pub(crate) fn supports_verify(&self) -> bool {
+        match self {
+            Self::Show {
+                nominators, watch, ..
+            } => !nominators && !watch,
+            Self::ShowAddress { .. }
+            | Self::Announce { .. }
+            | Self::Kick { .. }
+            | Self::Chill { .. }
+            | Self::Payout { .. }
+            | Self::ReplaceKey { .. }
+            | Self::RotateKeys { .. }
+            | Self::CheckKeys { .. }
+            | Self::SetPayee { .. }
+            | Self::Address { .. } => true,
+        }
+    }
+
+    /// Whether the command supports `--at`
+    pub(crate) fn supports_at(&self) -> bool {
+        match self {
+            Self::ShowAddress { .. } => true,
+            Self::Show { watch, .. } => !watch,
+            Self::Announce { .. }
+            | Self::Kick { .. }
+            | Self::Chill { .. }
+            | Self::Payout { .. }
+            | Self::ReplaceKey { .. }
+            | Self::RotateKeys { .. }
+            | Self::CheckKeys { .. }
+            | Self::SetPayee { .. }
+            | Self::Address { .. } => false,
+        }
+    }
+}
+
 fn parse_keys(buffer: &str) -> Result<SessionKeys, Error> {
     let buffer: &[u8] = buffer.as_ref();
     if !buffer.starts_with(b"0x") {
@@ -76,36 +221,484 @@ fn parse_keys(buffer: &str) -> Result<SessionKeys, Error> {
     Decode::decode(&mut &*bytes).map_err(|e| Box::new(e) as _)
 }
 
+/// The stash account controlled by `controller` at block `at`
+async fn stash_of(
+    client: &Client<KusamaRuntime>,
+    controller: &AccountId,
+    at: At<'_>,
+) -> Result<AccountId, Error> {
+    let store = LedgerStore {
+        controller: controller.clone(),
+    };
+    let StakingLedger { stash, .. } = fetch(client, &store, at)
+        .await?
+        .ok_or_else(|| format!("No validator account found for controller {}", controller))?;
+    Ok(stash)
+}
+
+/// Display the status of the validators with controllers `controllers` at
+/// block `at`, and their nominators if `nominators` is set
+async fn display_show(
+    client: &Client<KusamaRuntime>,
+    nominators: bool,
+    controllers: &[AccountId],
+    network: Ss58AddressFormat,
+    at: At<'_>,
+) -> Result<String, Error> {
+    let (era, validators) = join(
+        display_era(client, at),
+        crate::common::display_validators(client, controllers, network, at),
+    )
+    .await;
+    let mut output = format!("{}\n{}", era?, validators?);
+    if nominators {
+        let stores: Vec<_> = controllers
+            .iter()
+            .map(|controller| LedgerStore {
+                controller: controller.clone(),
+            })
+            .collect();
+        let stashes: Vec<_> = fetch_many(client, &stores, at)
+            .await?
+            .into_iter()
+            .flatten()
+            .map(|ledger| ledger.stash)
+            .collect();
+        output += &display_backing(client, &stashes, network, at).await?;
+    }
+    Ok(output)
+}
+
+/// Print the status of the validators with controllers `controllers` at block
+/// `at` as JSON
+async fn print_show_json(
+    client: &Client<KusamaRuntime>,
+    controllers: &[AccountId],
+    network: Ss58AddressFormat,
+    at: At<'_>,
+) -> Result<(), Error> {
+    let (progress, validators) = join(
+        era_progress(client, at),
+        crate::common::validators_json(client, controllers, network, at),
+    )
+    .await;
+    print_json(&serde_json::json!({
+        "era": EraJson::from(&progress?),
+        "validators": validators?,
+    }))
+}
+
+/// Format the exposure in the active era of each validator in `stashes`,
+/// along with the nominators that nominate it, at block `at`
+async fn display_backing(
+    client: &Client<KusamaRuntime>,
+    stashes: &[AccountId],
+    network: Ss58AddressFormat,
+    at: At<'_>,
+) -> Result<String, Error> {
+    let era = active_era(client, at).await?;
+    let exposures: Vec<_> = stashes
+        .iter()
+        .map(|stash| ErasStakersStore {
+            era,
+            stash: stash.clone(),
+        })
+        .collect();
+    let clipped: Vec<_> = stashes
+        .iter()
+        .map(|stash| ErasStakersClippedStore {
+            era,
+            stash: stash.clone(),
+        })
+        .collect();
+    let (exposures, clipped, nominations) = join3(
+        fetch_many(client, &exposures, at),
+        fetch_many(client, &clipped, at),
+        all_nominations(client, at),
+    )
+    .await;
+    let nominations = nominations?;
+    let properties = client.properties();
+    let mut output = String::new();
+    for ((stash, exposure), clipped) in stashes.iter().zip(exposures?).zip(clipped?) {
+        let (exposure, clipped) = (exposure.unwrap_or_default(), clipped.unwrap_or_default());
+        writeln!(
+            output,
+            "    Backing of validator {} in era {}:",
+            stash.to_ss58check_with_version(network),
+            era
+        )?;
+        if exposure.total == 0 {
+            output += "        Not in the active set\n";
+        } else {
+            writeln!(
+                output,
+                "        Own stake: {}\n        Total stake: {}\n        Nominators in the \
+                 exposure: {}",
+                Balance::new(exposure.own, properties),
+                Balance::new(exposure.total, properties),
+                exposure.others.len()
+            )?;
+            for IndividualExposure { who, value } in &exposure.others {
+                let paid = clipped
+                    .others
+                    .iter()
+                    .any(|individual| &individual.who == who);
+                writeln!(
+                    output,
+                    "            {} {}{}",
+                    who.to_ss58check_with_version(network),
+                    Balance::new(*value, properties),
+                    if paid {
+                        ""
+                    } else {
+                        " (oversubscribed: not paid)"
+                    }
+                )?;
+            }
+        }
+        let targeting: Vec<_> = nominations
+            .iter()
+            .filter(|(_, nominations)| nominations.targets.contains(stash))
+            .collect();
+        writeln!(
+            output,
+            "        Nominators currently nominating it: {}",
+            targeting.len()
+        )?;
+        for (nominator, nominations) in targeting {
+            let exposed = exposure
+                .others
+                .iter()
+                .any(|individual| &individual.who == nominator);
+            writeln!(
+                output,
+                "            {} (since era {}){}",
+                nominator.to_ss58check_with_version(network),
+                nominations.submitted_in,
+                if exposed {
+                    ""
+                } else {
+                    " (not in the exposure)"
+                }
+            )?;
+        }
+        output += "\n";
+    }
+    Ok(output)
+}
+
+/// Compare the session keys of the validator with stash `stash` with those on
+/// the node at `node`, and print which differ.  Fails if the node lacks any of
+/// the keys.  The keys of the validator are read at block `at`.
+async fn check_keys(
+    client: &Client<KusamaRuntime>,
+    stash: AccountId,
+    node: &str,
+    network: Ss58AddressFormat,
+    at: At<'_>,
+) -> Result<(), Error> {
+    let rpc = crate::rpc::connect(node).await?;
+    let next = NextKeysStore {
+        validator_id: stash.clone(),
+    };
+    let queued = QueuedKeysStore {
+        _runtime: PhantomData,
+    };
+    let (next, queued) = join(
+        fetch(client, &next, at),
+        fetch_or_default(client, &queued, at),
+    )
+    .await;
+    let queued = queued?
+        .into_iter()
+        .find(|(validator, _)| *validator == stash)
+        .map(|(_, keys)| keys);
+    let stash = stash.to_ss58check_with_version(network);
+    let next = next?.ok_or_else(|| format!("Validator {} has no session keys set", stash))?;
+    let next_keys = session_keys(&next);
+    let on_node = next_keys
+        .iter()
+        .map(|key| crate::rpc::has_key(&rpc, Bytes(key.public.clone()), key.key_type));
+    let (all_on_node, on_node) = join(
+        crate::rpc::has_session_keys(&rpc, Bytes(next.encode())),
+        try_join_all(on_node),
+    )
+    .await;
+    let (all_on_node, on_node) = (all_on_node?, on_node?);
+    let queued_keys = queued.as_ref().map(session_keys);
+    println!("Session keys of validator {}:", stash);
+    let mut missing = vec![];
+    let mut changed = vec![];
+    for (index, (key, on_node)) in next_keys.iter().zip(on_node).enumerate() {
+        print!(
+            "    {}: {} ({} node {})",
+            key.name,
+            format_key(&key.public),
+            if on_node { "on" } else { "NOT on" },
+            node
+        );
+        if !on_node {
+            missing.push(key.name)
+        }
+        match queued_keys.as_ref().map(|queued| &queued[index]) {
+            Some(queued) if queued.public != key.public => {
+                changed.push(key.name);
+                println!(", replacing queued key {}", format_key(&queued.public))
+            }
+            _ => println!(),
+        }
+    }
+    match queued {
+        None => println!(
+            "The validator is not in the validator set of the next session.  Its keys will take \
+             effect when it is next elected."
+        ),
+        Some(_) if changed.is_empty() => {
+            println!("These keys are queued, and take effect at the start of the next session.")
+        }
+        Some(_) => println!(
+            "The keys queued for the next session differ for: {}.  The new keys take effect in \
+             the session after the next one.",
+            changed.join(", ")
+        ),
+    }
+    if missing.is_empty() && all_on_node {
+        println!("Node {} has all of the session keys.", node);
+        Ok(())
+    } else if missing.is_empty() {
+        Err(format!(
+            "Node {} does not have all of the session keys, but has each individual key",
+            node
+        )
+        .into())
+    } else {
+        Err(format!(
+            "Node {} is missing the session keys for: {}",
+            node,
+            missing.join(", ")
+        )
+        .into())
+    }
+}
+
+/// The preferences at block `at` of the validator controlled by `controller`.
+/// If the validator has chilled, these are its preferences in the active era.
+async fn current_prefs(
+    client: &Client<KusamaRuntime>,
+    controller: &AccountId,
+    at: At<'_>,
+) -> Result<Option<ValidatorPrefs>, Error> {
+    let stash = stash_of(client, controller, at).await?;
+    let store = ValidatorsStore {
+        stash: stash.clone(),
+    };
+    if let Some(prefs) = fetch(client, &store, at).await? {
+        return Ok(Some(prefs))
+    }
+    let store = ErasValidatorPrefsStore {
+        era: active_era(client, at).await?,
+        stash,
+    };
+    Ok(fetch(client, &store, at).await?)
+}
+
+/// Check `commission` against the minimum commission of the chain at block
+/// `at`, and warn if it is much larger than the `current` commission.
+async fn check_commission(
+    client: &Client<KusamaRuntime>,
+    commission: Perbill,
+    current: Option<ValidatorPrefs>,
+    at: At<'_>,
+) -> Result<(), Error> {
+    let store = MinCommissionStore {
+        _runtime: PhantomData,
+    };
+    // Older runtimes have no minimum commission.
+    let min_commission = if store.key(client.metadata()).is_err() {
+        Perbill::zero()
+    } else {
+        fetch(client, &store, at).await?.unwrap_or_default()
+    };
+    if commission < min_commission {
+        return Err(format!(
+            "Commission {} is less than the minimum commission of {}",
+            format_commission(commission),
+            format_commission(min_commission)
+        )
+        .into())
+    }
+    if let Some(current) = current {
+        let increase = commission
+            .deconstruct()
+            .saturating_sub(current.commission.deconstruct());
+        if increase >= COMMISSION_JUMP_WARNING {
+            eprintln!(
+                "Warning: commission will increase from {} to {}",
+                format_commission(current.commission),
+                format_commission(commission)
+            )
+        }
+    }
+    Ok(())
+}
+
+/// Claim the payouts of the validator at `index`, or of every validator on the
+/// device if `index` is `None`, for `eras` (or every unclaimed era if empty).
+/// Every validator is checked before anything is claimed, so that a bad era
+/// does not leave some validators paid and others not.
+async fn payout(
+    client: &Client<KusamaRuntime>,
+    keystore: &super::HardStore,
+    index: Option<u32>,
+    eras: &[u32],
+    network: Ss58AddressFormat,
+    at: At<'_>,
+) -> Result<(), Error> {
+    let indices = match index {
+        Some(index) => vec![index],
+        None => {
+            let validators = crate::common::fetch_validators(
+                client,
+                AddressSource::Device(None, keystore),
+                network,
+                AccountType::Validator,
+                at,
+            )
+            .await?;
+            (1..=u32::try_from(validators.len())?).collect()
+        }
+    };
+    let mut claims = vec![];
+    for index in indices {
+        let path = LedgeracioPath::new(network, AccountType::Validator, index)?;
+        let signer = keystore.signer(path).await?;
+        let claim = crate::payouts::plan_payouts(client, &signer, eras, network, at).await?;
+        claims.push((index, signer, claim))
+    }
+    let total = claims.len();
+    let mut failed = 0;
+    for (index, signer, claim) in claims {
+        if let Err(e) = crate::payouts::claim_payouts(client, &signer, claim, network).await {
+            eprintln!("Failed to claim the payouts of validator {}: {}", index, e);
+            failed += 1
+        }
+    }
+    if failed == 0 {
+        Ok(())
+    } else {
+        Err(format!("{} of {} payout claims failed", failed, total).into())
+    }
+}
+
 pub(crate) async fn main<T: FnOnce() -> Result<super::HardStore, Error>>(
     cmd: Validator,
     client: Pin<Box<dyn Future<Output = Result<Client<KusamaRuntime>, Error>>>>,
     network: Ss58AddressFormat,
     keystore: T,
+    output: OutputFormat,
+    at: At<'_>,
 ) -> Result<Option<H256>, Error> {
     match cmd {
         Validator::ShowAddress {
             address: (stash, provided_network),
         } => {
-            ledgeracio::validate_network("", provided_network, network)?;
+            validate_network("", provided_network, network)?;
             let client = client.await?;
-            let controller = match client.fetch(&BondedStore { stash }, None).await? {
+            let controller = match fetch(&client, &BondedStore { stash }, at).await? {
                 Some(controller) => controller,
                 None => return Err("Controller not found for stash".to_owned().into()),
             };
-            crate::common::display_validators(&client, &[controller], network).await?;
+            match output {
+                OutputFormat::Json => print_show_json(&client, &[controller], network, at).await?,
+                OutputFormat::Text => print!(
+                    "{}",
+                    display_show(&client, false, &[controller], network, at).await?
+                ),
+            }
             Ok(None)
         }
-        Validator::Announce { index, commission } => {
+        Validator::Announce {
+            index,
+            commission,
+            blocked,
+            unblocked,
+        } => {
             let path = LedgeracioPath::new(network, AccountType::Validator, index)?;
-            let commission = commission.unwrap_or(1_000_000_000);
-            if commission > 1_000_000_000 {
-                return Err(format!("Commission {} too large (limit is 10⁹)", commission).into())
+            if let Some(commission) = commission {
+                if commission > 1_000_000_000 {
+                    return Err(format!("Commission {} too large (limit is 10⁹)", commission).into())
+                }
             }
-            let prefs = ValidatorPrefs {
-                commission: Perbill::from_parts(commission),
+            let signer = keystore()?.signer(path).await?;
+            let client = client.await?;
+            let current = current_prefs(&client, signer.account_id(), at).await?;
+            let mut prefs = match (commission, current) {
+                (Some(commission), current) => ValidatorPrefs {
+                    commission: Perbill::from_parts(commission),
+                    blocked: current.map_or(false, |prefs| prefs.blocked),
+                },
+                (None, Some(current)) => current,
+                (None, None) => {
+                    return Err(
+                        "No existing commission found for this validator, so one must be specified"
+                            .to_owned()
+                            .into(),
+                    )
+                }
             };
+            if blocked {
+                prefs.blocked = true
+            } else if unblocked {
+                prefs.blocked = false
+            }
+            check_commission(&client, prefs.commission, current, at).await?;
+            println!(
+                "Announcing intention to validate with commission {}{}",
+                format_commission(prefs.commission),
+                if prefs.blocked {
+                    ", blocking new nominations"
+                } else {
+                    ""
+                }
+            );
+            Ok(Some(client.validate(&signer, prefs).await?))
+        }
+        Validator::Kick { index, nominators } => {
+            let mut who = vec![];
+            for (nominator, provided_network) in nominators {
+                validate_network(
+                    &nominator.to_ss58check_with_version(provided_network.try_into().unwrap()),
+                    provided_network,
+                    network,
+                )?;
+                who.push(nominator)
+            }
+            let path = LedgeracioPath::new(network, AccountType::Validator, index)?;
             let signer = keystore()?.signer(path).await?;
-            Ok(Some(client.await?.validate(&signer, prefs).await?))
+            let client = client.await?;
+            let stash = stash_of(&client, signer.account_id(), at).await?;
+            for nominator in &who {
+                let store = NominatorsStore {
+                    stash: nominator.clone(),
+                };
+                let nominates = fetch(&client, &store, at)
+                    .await?
+                    .map_or(false, |nominations| nominations.targets.contains(&stash));
+                if !nominates {
+                    return Err(format!(
+                        "{} does not nominate {}",
+                        nominator.to_ss58check_with_version(network),
+                        stash.to_ss58check_with_version(network)
+                    )
+                    .into())
+                }
+            }
+            Ok(Some(client.kick(&signer, who).await?))
+        }
+        Validator::Payout { index, era } => {
+            payout(&client.await?, &keystore()?, index, &era, network, at).await?;
+            Ok(None)
         }
         Validator::Chill { index } => {
             let path = LedgeracioPath::new(network, AccountType::Validator, index)?;
@@ -117,7 +710,48 @@ pub(crate) async fn main<T: FnOnce() -> Result<super::HardStore, Error>>(
             let signer = keystore()?.signer(path).await?;
             Ok(Some(client.await?.set_keys(&signer, keys, vec![]).await?))
         }
-        Validator::Show { index } => {
+        Validator::RotateKeys { index, node } => {
+            let path = LedgeracioPath::new(network, AccountType::Validator, index)?;
+            let rpc = crate::rpc::connect(&node).await?;
+            let bytes = crate::rpc::rotate_keys(&rpc).await?;
+            if !crate::rpc::has_session_keys(&rpc, bytes.clone()).await? {
+                return Err(format!(
+                    "Node {} does not have the session keys it just generated",
+                    node
+                )
+                .into())
+            }
+            let mut input = &*bytes.0;
+            let keys = SessionKeys::decode(&mut input)?;
+            if !input.is_empty() {
+                return Err(format!(
+                    "Node {} returned session keys of the wrong length.  Is it running the same \
+                     chain?",
+                    node
+                )
+                .into())
+            }
+            print!(
+                "Node {} generated new session keys:\n{}",
+                node,
+                format_session_keys(&keys, "    ")
+            );
+            let signer = keystore()?.signer(path).await?;
+            Ok(Some(client.await?.set_keys(&signer, keys, vec![]).await?))
+        }
+        Validator::CheckKeys { index, node } => {
+            let path = LedgeracioPath::new(network, AccountType::Validator, index)?;
+            let signer = keystore()?.signer(path).await?;
+            let client = client.await?;
+            let stash = stash_of(&client, signer.account_id(), at).await?;
+            check_keys(&client, stash, &node, network, at).await?;
+            Ok(None)
+        }
+        Validator::Show {
+            index,
+            nominators,
+            watch,
+        } => {
             let client = client.await?;
             // These are *controller*, not *stash*, accounts.
             let validators = crate::common::fetch_validators(
@@ -125,19 +759,36 @@ pub(crate) async fn main<T: FnOnce() -> Result<super::HardStore, Error>>(
                 AddressSource::Device(index, &keystore()?),
                 network,
                 AccountType::Validator,
+                at,
             )
             .await?;
-            crate::common::display_validators(&client, &*validators, network).await?;
+            let (client, validators) = (&client, &*validators);
+            let render = move |hash| {
+                display_show(client, nominators, validators, network, At { hash, ..at })
+            };
+            if watch {
+                crate::watch::watch(client, at.rpc, validators, network, render).await?
+            } else if output == OutputFormat::Json {
+                print_show_json(client, validators, network, at).await?
+            } else {
+                print!("{}", render(at.hash).await?)
+            }
             Ok(None)
         }
         Validator::SetPayee { index, target } => {
+            let target = validate_reward_destination(target, network)?;
+            println!(
+                "Setting payee to {}",
+                format_reward_destination(&target, network)
+            );
             let path = LedgeracioPath::new(network, AccountType::Validator, index)?;
             let signer = keystore()?.signer(path).await?;
             client.await?.set_payee(&signer, target).await?;
             Ok(None)
         }
         Validator::Address { index } => {
-            crate::display_path(AccountType::Validator, &keystore()?, network, index).await?;
+            crate::display_path(AccountType::Validator, &keystore()?, network, index, output)
+                .await?;
             Ok(None)
         }
     }
@@ -0,0 +1,425 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of ledgeracio.
+//
+// ledgeracio is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// ledgeracio is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with ledgeracio.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Following finalized blocks, for the `--watch` option of the `show`
+//! commands
+
+use crate::{common::{active_era, fetch_many, fetch_raw, At},
+            rpc::BlockId,
+            staking::{ErasStakersClippedStore, ValidatorsStore},
+            Error};
+use codec::Decode;
+use futures::future::join;
+use ledgeracio::Balance;
+use log::warn;
+use std::{collections::HashSet, future::Future, marker::PhantomData};
+use substrate_subxt::{sp_core::{crypto::{AccountId32 as AccountId, Ss58AddressFormat, Ss58Codec},
+                                storage::{StorageData, StorageKey},
+                                twox_128, H256},
+                      staking::{ActiveEraStore, BondedStore, CurrentEraStore, LedgerStore,
+                                NominatorsStore, PayeeStore},
+                      Client, EventsDecoder, KusamaRuntime, Raw, RawEvent, Store, SystemProperties};
+
+/// The storage read by the `show` commands for a set of controllers
+struct Watched {
+    /// The stashes of the controllers
+    stashes: Vec<AccountId>,
+    /// The indices in `keys` of the `Validators` and `Nominators` keys of
+    /// each stash, in the same order as `stashes`
+    intentions: Vec<(usize, usize)>,
+    /// Every key whose value affects the output, including the preferences
+    /// and exposure in the active era of every validator that is shown
+    keys: Vec<StorageKey>,
+}
+
+impl Watched {
+    /// Find what to watch for `controllers`, using the ledgers at block `at`
+    async fn new(
+        client: &Client<KusamaRuntime>,
+        controllers: &[AccountId],
+        at: At<'_>,
+    ) -> Result<Self, Error> {
+        let metadata = client.metadata();
+        let ledgers: Vec<_> = controllers
+            .iter()
+            .map(|controller| LedgerStore {
+                controller: controller.clone(),
+            })
+            .collect();
+        let mut keys = vec![
+            ActiveEraStore {
+                _runtime: PhantomData,
+            }
+            .key(metadata)?,
+            CurrentEraStore {
+                _runtime: PhantomData,
+            }
+            .key(metadata)?,
+        ];
+        for ledger in &ledgers {
+            keys.push(ledger.key(metadata)?)
+        }
+        let stashes: Vec<_> = fetch_many(client, &ledgers, at)
+            .await?
+            .into_iter()
+            .flatten()
+            .map(|ledger| ledger.stash)
+            .collect();
+        let mut intentions = vec![];
+        for stash in &stashes {
+            keys.push(
+                BondedStore {
+                    stash: stash.clone(),
+                }
+                .key(metadata)?,
+            );
+            keys.push(
+                PayeeStore {
+                    stash: stash.clone(),
+                }
+                .key(metadata)?,
+            );
+            let (validator, nominator) = intention_keys(client, stash)?;
+            keys.push(validator);
+            keys.push(nominator);
+            intentions.push((keys.len() - 2, keys.len() - 1))
+        }
+        // The status and exposure of the validators that the stashes nominate,
+        // or of the stashes themselves, are shown too.
+        let nominators: Vec<_> = stashes
+            .iter()
+            .map(|stash| NominatorsStore {
+                stash: stash.clone(),
+            })
+            .collect();
+        let (nominations, era) =
+            join(fetch_many(client, &nominators, at), active_era(client, at)).await;
+        let mut validators: Vec<_> = nominations?
+            .into_iter()
+            .flatten()
+            .flat_map(|nominations| nominations.targets)
+            .chain(stashes.iter().cloned())
+            .collect();
+        validators.sort_unstable();
+        validators.dedup();
+        let era = era?;
+        for stash in validators {
+            keys.push(
+                ValidatorsStore {
+                    stash: stash.clone(),
+                }
+                .key(metadata)?,
+            );
+            keys.push(ErasStakersClippedStore { era, stash }.key(metadata)?)
+        }
+        Ok(Self {
+            stashes,
+            intentions,
+            keys,
+        })
+    }
+}
+
+/// A staking event that concerns a stash
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum StakingEvent {
+    /// A stash was paid a reward
+    Reward(AccountId, u128),
+    /// A stash was slashed
+    Slash(AccountId, u128),
+    /// A nominator was removed from the nominators of a validator
+    Kicked {
+        nominator: AccountId,
+        validator: AccountId,
+    },
+    /// A stash was chilled.  Older runtimes do not emit this event.
+    Chilled(AccountId),
+}
+
+impl StakingEvent {
+    /// Decode an event, returning `None` if it is not a staking event
+    pub(crate) fn decode(
+        module: &str,
+        variant: &str,
+        mut data: &[u8],
+    ) -> Result<Option<Self>, Error> {
+        if module != "Staking" {
+            return Ok(None)
+        }
+        Ok(match variant {
+            "Reward" => {
+                let (stash, amount) = Decode::decode(&mut data)?;
+                Some(Self::Reward(stash, amount))
+            }
+            "Slash" => {
+                let (stash, amount) = Decode::decode(&mut data)?;
+                Some(Self::Slash(stash, amount))
+            }
+            "Kicked" => {
+                let (nominator, validator) = Decode::decode(&mut data)?;
+                Some(Self::Kicked {
+                    nominator,
+                    validator,
+                })
+            }
+            "Chilled" => Some(Self::Chilled(Decode::decode(&mut data)?)),
+            _ => None,
+        })
+    }
+
+    /// The stash that the event concerns, if it is one of `stashes`
+    pub(crate) fn stash<'a>(&'a self, stashes: &[AccountId]) -> Option<&'a AccountId> {
+        match self {
+            Self::Reward(stash, _) | Self::Slash(stash, _) | Self::Chilled(stash) => Some(stash),
+            Self::Kicked { nominator, .. } if stashes.contains(nominator) => Some(nominator),
+            Self::Kicked { validator, .. } => Some(validator),
+        }
+        .filter(|stash| stashes.contains(stash))
+    }
+
+    /// Format the event as a line of text
+    pub(crate) fn format(
+        &self,
+        properties: &SystemProperties,
+        network: Ss58AddressFormat,
+    ) -> String {
+        let address = |account: &AccountId| account.to_ss58check_with_version(network);
+        match self {
+            Self::Reward(stash, amount) => format!(
+                "Reward: {} was paid {}",
+                address(stash),
+                Balance::new(*amount, properties)
+            ),
+            Self::Slash(stash, amount) => format!(
+                "Slash: {} was slashed {}",
+                address(stash),
+                Balance::new(*amount, properties)
+            ),
+            Self::Kicked {
+                nominator,
+                validator,
+            } => format!(
+                "Kicked: {} was removed from the nominators of {}",
+                address(nominator),
+                address(validator)
+            ),
+            Self::Chilled(stash) => format!("Chilled: {} was chilled", address(stash)),
+        }
+    }
+}
+
+/// Fetch and decode the events of block `at`, which has number `number`.  If
+/// the events cannot be decoded, a warning is logged and no events are
+/// returned.
+pub(crate) async fn block_events(
+    client: &Client<KusamaRuntime>,
+    rpc: &jsonrpsee::Client,
+    at: H256,
+    number: u32,
+) -> Result<Vec<RawEvent>, Error> {
+    let key = StorageKey([twox_128(b"System"), twox_128(b"Events")].concat());
+    let data = match fetch_raw(rpc, &[key], at).await?.pop().flatten() {
+        Some(StorageData(data)) => data,
+        None => return Ok(vec![]),
+    };
+    let decoder = EventsDecoder::<KusamaRuntime>::new(client.metadata().clone());
+    match decoder.decode_events(&mut &*data) {
+        Ok(events) => Ok(events
+            .into_iter()
+            .filter_map(|(_, event)| match event {
+                Raw::Event(event) => Some(event),
+                Raw::Error(_) => None,
+            })
+            .collect()),
+        Err(e) => {
+            // Events of modules whose types we do not know cannot be decoded,
+            // and neither can any later events in the same block.
+            warn!("Could not decode the events of block {}: {}", number, e);
+            Ok(vec![])
+        }
+    }
+}
+
+/// The storage keys of the intentions of `stash` to validate and to nominate.
+/// A chill removes whichever of them is present.
+pub(crate) fn intention_keys(
+    client: &Client<KusamaRuntime>,
+    stash: &AccountId,
+) -> Result<(StorageKey, StorageKey), Error> {
+    let metadata = client.metadata();
+    let validator = ValidatorsStore {
+        stash: stash.clone(),
+    };
+    let nominator = NominatorsStore {
+        stash: stash.clone(),
+    };
+    Ok((validator.key(metadata)?, nominator.key(metadata)?))
+}
+
+/// Print the staking events of block `at` that concern one of `stashes`.
+/// Returns the stashes that were chilled by an event.
+async fn print_events(
+    client: &Client<KusamaRuntime>,
+    rpc: &jsonrpsee::Client,
+    at: H256,
+    number: u32,
+    stashes: &[AccountId],
+    network: Ss58AddressFormat,
+) -> Result<HashSet<AccountId>, Error> {
+    let mut chilled = HashSet::new();
+    for event in block_events(client, rpc, at, number).await? {
+        let event = match StakingEvent::decode(&event.module, &event.variant, &event.data) {
+            Ok(Some(event)) => event,
+            Ok(None) => continue,
+            Err(e) => {
+                warn!(
+                    "Could not decode a {}::{} event in block {}: {}",
+                    event.module, event.variant, number, e
+                );
+                continue
+            }
+        };
+        if let Some(stash) = event.stash(stashes) {
+            println!(
+                "[#{}] {}",
+                number,
+                event.format(client.properties(), network)
+            );
+            if let StakingEvent::Chilled(_) = event {
+                chilled.insert(stash.clone());
+            }
+        }
+    }
+    Ok(chilled)
+}
+
+/// Follow finalized blocks, printing the output of `render` at the first
+/// block and again whenever the storage it reads from changes.  `render` is
+/// called with the hash of the block to read.  Staking events that concern the
+/// stashes of `controllers` are printed as they happen.  This runs until the
+/// connection to the node is lost.
+pub(crate) async fn watch<F, Fut>(
+    client: &Client<KusamaRuntime>,
+    rpc: &jsonrpsee::Client,
+    controllers: &[AccountId],
+    network: Ss58AddressFormat,
+    mut render: F,
+) -> Result<(), Error>
+where
+    F: FnMut(Option<H256>) -> Fut,
+    Fut: Future<Output = Result<String, Error>>,
+{
+    let mut heads = client.subscribe_finalized_blocks().await?;
+    let mut watched = Watched::new(client, controllers, At::best(rpc)).await?;
+    let (mut previous, mut last) = (None, None);
+    loop {
+        let head = heads.next().await.number;
+        // Finality can advance by several blocks at once, and the
+        // subscription only reports the last of them.
+        let first = last.map_or(head, |last: u32| last + 1);
+        last = Some(head);
+        for number in first..=head {
+            let at = crate::rpc::block_hash(rpc, BlockId::Number(number)).await?;
+            let chilled = print_events(client, rpc, at, number, &watched.stashes, network).await?;
+            let values = fetch_raw(rpc, &watched.keys, at).await?;
+            if previous.as_ref() == Some(&values) {
+                continue
+            }
+            if let Some(previous) = &previous {
+                print_chills(previous, &values, &watched, &chilled, number, network)
+            }
+            println!("=== Changed in block #{} ({:?}) ===", number, at);
+            print!("{}", render(Some(at)).await?);
+            // The stashes may have changed, and with them the keys to watch.
+            watched = Watched::new(client, controllers, At::block(rpc, at)).await?;
+            previous = Some(fetch_raw(rpc, &watched.keys, at).await?);
+        }
+    }
+}
+
+/// Print the chills in block `number` that no event reported, by looking for
+/// intentions that were in `previous` but are not in `values`.  Chills are
+/// only reported by events in newer runtimes.
+fn print_chills(
+    previous: &[Option<StorageData>],
+    values: &[Option<StorageData>],
+    watched: &Watched,
+    chilled: &HashSet<AccountId>,
+    number: u32,
+    network: Ss58AddressFormat,
+) {
+    let stopped = |i: usize| previous[i].is_some() && values[i].is_none();
+    for (stash, &(validator, nominator)) in watched.stashes.iter().zip(&watched.intentions) {
+        let kind = if stopped(validator) {
+            "validating"
+        } else if stopped(nominator) {
+            "nominating"
+        } else {
+            continue
+        };
+        if !chilled.contains(stash) {
+            println!(
+                "[#{}] Chilled: {} is no longer {}",
+                number,
+                stash.to_ss58check_with_version(network),
+                kind
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use codec::Encode;
+
+    #[test]
+    fn staking_events_work() {
+        let properties = SystemProperties {
+            ss58_format: 2,
+            token_decimals: 12,
+            token_symbol: "KSM".to_owned(),
+        };
+        let (ours, theirs) = (AccountId::from([1; 32]), AccountId::from([2; 32]));
+        let stashes = [ours.clone()];
+        let network = Ss58AddressFormat::KusamaAccount;
+        let decode =
+            |module, variant, data: Vec<u8>| StakingEvent::decode(module, variant, &data).unwrap();
+        let reward = decode(
+            "Staking",
+            "Reward",
+            (ours.clone(), 1_500_000_000_000_u128).encode(),
+        )
+        .unwrap();
+        assert_eq!(reward.stash(&stashes), Some(&ours));
+        assert_eq!(
+            reward.format(&properties, network),
+            format!(
+                "Reward: {} was paid 1.5 KSM",
+                ours.to_ss58check_with_version(network)
+            )
+        );
+        let slash = decode("Staking", "Slash", (theirs.clone(), 1_u128).encode()).unwrap();
+        assert_eq!(slash.stash(&stashes), None);
+        let kicked = decode("Staking", "Kicked", (theirs.clone(), ours.clone()).encode()).unwrap();
+        assert_eq!(kicked, StakingEvent::Kicked {
+            nominator: theirs,
+            validator: ours.clone(),
+        });
+        assert_eq!(kicked.stash(&stashes), Some(&ours));
+        assert_eq!(decode("Balances", "Reward", (ours, 1_u128).encode()), None);
+    }
+}
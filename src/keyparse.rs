@@ -16,16 +16,28 @@
 
 //! Routines for parsing public and secret keys
 
-use super::{KEY_MAGIC, KEY_VERSION};
+use super::Error;
 use ed25519_dalek::{ExpandedSecretKey, Keypair, PublicKey};
-use ledgeracio::Error;
 use regex::bytes::Regex;
 use std::{convert::{TryFrom, TryInto},
           str};
 use substrate_subxt::sp_core::crypto::Ss58AddressFormat;
 
+/// The version of keys supported
+pub const KEY_VERSION: u8 = 1;
+
+/// The magic number at the beginning of a secret key
+pub const KEY_MAGIC: &[u8] = &*b"Ledgeracio Secret Key";
+
 /// Parse a Ledgeracio secret key file
-pub(crate) fn parse_secret(secret: &[u8], network: Ss58AddressFormat) -> Result<Keypair, Error> {
+///
+/// See FORMATS.md for the format of this key.
+///
+/// # Errors
+///
+/// Fails if the key is malformed, is for a network other than `network`, or
+/// if its secret and public halves do not match.
+pub fn parse_secret(secret: &[u8], network: Ss58AddressFormat) -> Result<Keypair, Error> {
     if secret.len() != 88 {
         return Err(format!("Ledgeracio secret keys are 88 bytes, not {}", secret.len()).into())
     }
@@ -66,7 +78,11 @@ pub(crate) fn parse_secret(secret: &[u8], network: Ss58AddressFormat) -> Result<
 /// Parse a Ledgeracio public key
 ///
 /// See FORMATS.md for the format of this key.
-pub(crate) fn parse_public(unparsed: &[u8]) -> Result<(PublicKey, Ss58AddressFormat), Error> {
+///
+/// # Errors
+///
+/// Fails if the key is malformed or the network is unknown.
+pub fn parse_public(unparsed: &[u8]) -> Result<(PublicKey, Ss58AddressFormat), Error> {
     let re = Regex::new(
         r"^untrusted comment: Ledgeracio v2 network ([[:alpha:]]+) public key\n([[:alnum:]/+]+)\n$",
     )
@@ -20,12 +20,18 @@
 #![allow(clippy::non_ascii_literal)]
 #![forbid(unsafe_code)]
 
+mod balance;
 mod derivation;
 mod hardstore;
+mod keyparse;
+mod parser;
 
+pub use balance::{pad, Balance};
 use codec::Encode;
 pub use derivation::{AccountType, LedgeracioPath};
 pub use hardstore::{HardSigner, HardStore};
+pub use keyparse::{parse_public, parse_secret, KEY_MAGIC, KEY_VERSION};
+pub use parser::{inspect as inspect_allowlist, parse as parse_allowlist, parse_targets};
 
 #[cfg(not(unix))]
 compile_error!("Only *nix-like platforms are supported");
@@ -39,7 +45,7 @@ pub use structopt::StructOpt;
 pub use substrate_subxt::{sp_core,
                           sp_core::crypto::{Ss58AddressFormat, Ss58Codec},
                           staking::RewardDestination,
-                          Client, ClientBuilder, Signer};
+                          Client, ClientBuilder, Signer, SystemProperties};
 
 pub type Error = Box<dyn std::error::Error + Send + Sync>;
 
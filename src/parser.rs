@@ -21,6 +21,53 @@ use std::{convert::TryFrom,
           io::{prelude::*, Error, ErrorKind}};
 use substrate_subxt::sp_core::crypto::{AccountId32 as AccountId, Ss58AddressFormat, Ss58Codec};
 
+/// The entries of a textual allowlist read from `reader`: every line that is
+/// neither blank nor a comment, trimmed, along with its 1-based line number
+fn entries<T: BufRead>(reader: T) -> impl Iterator<Item = std::io::Result<(usize, String)>> {
+    reader
+        .lines()
+        .enumerate()
+        .filter_map(|(index, line)| match line {
+            Err(e) => Some(Err(e)),
+            Ok(line) => {
+                let trimmed = line.trim();
+                if trimmed.starts_with(';') || trimmed.starts_with('#') || trimmed.is_empty() {
+                    None
+                } else {
+                    Some(Ok((index + 1, trimmed.to_owned())))
+                }
+            }
+        })
+}
+
+/// Parse `address`, found on line `line`, and check that it is for `network`
+fn parse_entry(
+    address: &str,
+    network: Ss58AddressFormat,
+    line: usize,
+) -> std::io::Result<AccountId> {
+    let (account, address_type) = crate::parse_address(address).map_err(|i| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("parse error on line {}: {}", line, i),
+        )
+    })?;
+    crate::validate_network(address, address_type, network).map_err(|i| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("invalid network on line {}: {}", line, i),
+        )
+    })?;
+    Ok(account)
+}
+
+/// Compile the textual allowlist read from `reader` to binary format and sign
+/// it with `sk`.
+///
+/// # Errors
+///
+/// Fails if reading fails, or if any address is malformed or for a network
+/// other than `network`.
 pub fn parse<T: BufRead, U: Ss58Codec>(
     reader: T,
     network: Ss58AddressFormat,
@@ -30,25 +77,9 @@ pub fn parse<T: BufRead, U: Ss58Codec>(
 ) -> std::io::Result<Vec<u8>> {
     let mut v = vec![0; 72];
     v[..4].copy_from_slice(&nonce.to_le_bytes());
-    for (l, i) in reader.lines().enumerate() {
-        let i = i?;
-        let trimmed = i.trim_start().trim_end();
-        if trimmed.starts_with(';') || trimmed.starts_with('#') || trimmed.is_empty() {
-            continue
-        }
-        let (_address, address_type): (AccountId, _) =
-            ledgeracio::parse_address(trimmed).map_err(|i| {
-                Error::new(
-                    ErrorKind::InvalidData,
-                    format!("parse error on line {}: {}", l, i),
-                )
-            })?;
-        ledgeracio::validate_network(trimmed, address_type, network).map_err(|i| {
-            Error::new(
-                ErrorKind::InvalidData,
-                format!("invalid network on line {}: {}", l, i),
-            )
-        })?;
+    for entry in entries(reader) {
+        let (line, trimmed) = entry?;
+        parse_entry(&trimmed, network, line)?;
         let bytes = trimmed.as_bytes();
         let current_len = v.len();
         v.extend_from_slice(&[0_u8; 64]);
@@ -69,6 +100,42 @@ pub fn parse<T: BufRead, U: Ss58Codec>(
     Ok(v)
 }
 
+/// Parse a textual list of nomination targets.  The format is that of textual
+/// allowlists, except that an address may be followed by whitespace and a
+/// label, which is returned along with the address.
+///
+/// # Errors
+///
+/// Fails if reading fails, or if any address is malformed or for a network
+/// other than `network`.
+pub fn parse_targets<T: BufRead>(
+    reader: T,
+    network: Ss58AddressFormat,
+) -> std::io::Result<Vec<(AccountId, Option<String>)>> {
+    let mut targets = vec![];
+    for entry in entries(reader) {
+        let (line, trimmed) = entry?;
+        let (address, label) = match trimmed.find(char::is_whitespace) {
+            Some(end) => (
+                &trimmed[..end],
+                Some(trimmed[end..].trim_start().to_owned()),
+            ),
+            None => (&*trimmed, None),
+        };
+        let account = parse_entry(address, network, line)?;
+        targets.push((account, label))
+    }
+    Ok(targets)
+}
+
+/// Verify the signature of the binary allowlist read from `reader`.  Returns
+/// the nonce (formatted as `Nonce: <nonce>\n`), followed by the addresses in
+/// the allowlist.
+///
+/// # Errors
+///
+/// Fails if reading fails, the allowlist is malformed, any address is for a
+/// network other than `network`, or the signature is not valid for `pk`.
 pub fn inspect<T: BufRead, U: Ss58Codec>(
     mut reader: T,
     network: Ss58AddressFormat,
@@ -101,7 +168,7 @@ pub fn inspect<T: BufRead, U: Ss58Codec>(
                 format!("invalid UTF8 in address {}: {}", i, j),
             )
         })?;
-        let address_type = ledgeracio::parse_address::<AccountId>(trimmed)
+        let address_type = crate::parse_address::<AccountId>(trimmed)
             .map_err(|j| {
                 Error::new(
                     ErrorKind::InvalidData,
@@ -109,7 +176,7 @@ pub fn inspect<T: BufRead, U: Ss58Codec>(
                 )
             })?
             .1;
-        ledgeracio::validate_network(trimmed, address_type, network).map_err(|j| {
+        crate::validate_network(trimmed, address_type, network).map_err(|j| {
             Error::new(
                 ErrorKind::InvalidData,
                 format!("invalid network on line {}: {}", i, j),
@@ -187,10 +254,52 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parses_targets() {
+        const TARGETS: &[u8] = br#"
+# a comment
+5DArCreQ9Yk2HaGvxcRHS35qky3eXBD5BprPZQvbiJBfFY6Y
+   5GQvjFcJBCGTFeb2hvtQ9yRfbDNQajLJbW1yzgCra5uUTLvn   Some validator  
+        "#;
+        let targets = parse_targets(TARGETS, Ss58AddressFormat::SubstrateAccount).unwrap();
+        assert_eq!(
+            targets
+                .iter()
+                .map(|(account, label)| (account.to_ss58check(), label.as_deref()))
+                .collect::<Vec<_>>(),
+            &[
+                (
+                    "5DArCreQ9Yk2HaGvxcRHS35qky3eXBD5BprPZQvbiJBfFY6Y".to_owned(),
+                    None
+                ),
+                (
+                    "5GQvjFcJBCGTFeb2hvtQ9yRfbDNQajLJbW1yzgCra5uUTLvn".to_owned(),
+                    Some("Some validator")
+                ),
+            ][..]
+        );
+        assert!(parse_targets(TARGETS, Ss58AddressFormat::PolkadotAccount).is_err());
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        const TARGETS: &[u8] = br#"; a comment
+5DArCreQ9Yk2HaGvxcRHS35qky3eXBD5BprPZQvbiJBfFY6Y
+
+   not-an-address   label
+"#;
+        let error = parse_targets(TARGETS, Ss58AddressFormat::SubstrateAccount).unwrap_err();
+        assert!(
+            error.to_string().starts_with("parse error on line 4: "),
+            "{}",
+            error
+        );
+    }
+
     #[test]
     #[should_panic(
         expected = "called `Result::unwrap()` on an `Err` value: Custom { kind: InvalidData, \
-                    error: \"invalid network on line 1: Network mismatch: address \
+                    error: \"invalid network on line 2: Network mismatch: address \
                     5DArCreQ9Yk2HaGvxcRHS35qky3eXBD5BprPZQvbiJBfFY6Y is for network substrate, \
                     but you asked to use network polkadot\" }"
     )]